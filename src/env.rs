@@ -0,0 +1,259 @@
+//! A gym style environment for training agents on the game.
+//!
+//! ```no_run
+//! use rust_gaem::env::{PacmanEnv, ACTIONS};
+//! use rust_gaem::game::rules::Rules;
+//! use rust_gaem::game::{Direction, Parameters};
+//! use rust_gaem::sim::DiskLoader;
+//!
+//! let params = Parameters {
+//!     start_dir: Direction::Left,
+//!     map_file: "map.txt".to_string(),
+//!     campaign: None,
+//!     rules: Rules::default(),
+//! };
+//! let mut env = PacmanEnv::new(params, DiskLoader).unwrap();
+//! let mut observation = env.reset(0).unwrap();
+//! let mut done = false;
+//! while !done {
+//!     let action = ACTIONS[observation.cells.len() % ACTIONS.len()];
+//!     let (next, _reward, next_done, _info) = env.step(action);
+//!     observation = next;
+//!     done = next_done;
+//! }
+//! ```
+
+use crate::game::map::Tile;
+use crate::game::{Game, Input, Parameters, Stats};
+use crate::sim::{FileLoader, Sim, Simulator};
+
+/// The inputs an agent can choose from, quitting and pausing are left out.
+pub const ACTIONS: [Input; 5] = [
+    Input::None,
+    Input::Up,
+    Input::Down,
+    Input::Left,
+    Input::Right,
+];
+
+/// Reward for every life lost, on top of the change in score.
+pub const REWARD_DEATH: f64 = -500.0;
+/// Reward for clearing all pellets of a level, on top of the change in score.
+pub const REWARD_LEVEL_CLEAR: f64 = 1000.0;
+
+/// Values of the cells in an [`Observation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Cell {
+    Empty = 0,
+    Wall = 1,
+    House = 2,
+    Dot = 3,
+    PowerUp = 4,
+    Player = 5,
+    Ghost = 6,
+    FrightenedGhost = 7,
+    Fruit = 8,
+    /// A ghost that was eaten and returns to the house.
+    Eyes = 9,
+}
+
+/// The game as a grid of [`Cell`] values, row by row. The player is drawn on top of the
+/// ghosts, and the ghosts on top of the map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<u8>,
+}
+
+impl Observation {
+    fn new<RG: Simulator>(game: &Game<RG>) -> Self {
+        let map = game.map();
+        let mut cells = Vec::with_capacity(map.width * map.height);
+        for y in 0..map.height as i32 {
+            for x in 0..map.width as i32 {
+                let cell = match map.get(x, y) {
+                    Some(Tile::Wall) => Cell::Wall,
+                    Some(Tile::House) => Cell::House,
+                    Some(Tile::Dot) => Cell::Dot,
+                    Some(Tile::PowerUp) => Cell::PowerUp,
+                    Some(Tile::Empty) | None => Cell::Empty,
+                };
+                cells.push(cell as u8);
+            }
+        }
+        let mut observation = Observation {
+            width: map.width,
+            height: map.height,
+            cells,
+        };
+        if let Some((_, (x, y))) = game.bonus_fruit() {
+            observation.set(x, y, Cell::Fruit);
+        }
+        for g in game.ghosts() {
+            let cell = if g.eaten() {
+                Cell::Eyes
+            } else if g.frightened() {
+                Cell::FrightenedGhost
+            } else {
+                Cell::Ghost
+            };
+            observation.set(g.x(), g.y(), cell);
+        }
+        let (x, y, _) = game.player();
+        observation.set(x, y, Cell::Player);
+        observation
+    }
+
+    fn set(&mut self, x: i32, y: i32, cell: Cell) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.cells[y as usize * self.width + x as usize] = cell as u8;
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x < self.width {
+            self.cells.get(y * self.width + x).copied()
+        } else {
+            None
+        }
+    }
+}
+
+/// Extra information about a step, not meant to be used by the agent itself.
+#[derive(Debug, Clone, Copy)]
+pub struct StepInfo {
+    /// Number of frames played since the last reset.
+    pub frame: u64,
+    pub stats: Stats,
+    pub lives_lost: u8,
+    pub level_cleared: bool,
+}
+
+pub struct PacmanEnv<L: FileLoader + Default + Clone> {
+    params: Parameters,
+    loader: L,
+    game: Game<Sim<L>>,
+    frame: u64,
+    done: bool,
+}
+
+impl<L: FileLoader + Default + Clone> PacmanEnv<L> {
+    /// Create an environment playing with `params`, started with seed 0.
+    pub fn new(params: Parameters, loader: L) -> Result<Self, String> {
+        let game = Game::new(params.clone(), Sim::new(0, loader.clone()))?;
+        Ok(PacmanEnv {
+            params,
+            loader,
+            game,
+            frame: 0,
+            done: false,
+        })
+    }
+
+    /// Start a new game with `seed` and return the first observation.
+    pub fn reset(&mut self, seed: u64) -> Result<Observation, String> {
+        self.game = Game::new(self.params.clone(), Sim::new(seed, self.loader.clone()))?;
+        self.frame = 0;
+        self.done = false;
+        Ok(Observation::new(&self.game))
+    }
+
+    /// Play one frame with `action`, usually one of [`ACTIONS`]. The game is done when
+    /// the player has no lives left, quits or finishes the campaign, after that steps do
+    /// nothing until a reset.
+    pub fn step(&mut self, action: Input) -> (Observation, f64, bool, StepInfo) {
+        let before = self.game.stats();
+        if !self.done {
+            if self.game.input(action) {
+                self.done = true;
+            } else {
+                self.game.update();
+                self.frame += 1;
+            }
+        }
+        let after = self.game.stats();
+
+        let lives_lost = before.lives.saturating_sub(after.lives);
+        let level_cleared = after.level > before.level;
+        let mut reward = f64::from(after.score) - f64::from(before.score);
+        reward += REWARD_DEATH * f64::from(lives_lost);
+        if level_cleared {
+            reward += REWARD_LEVEL_CLEAR;
+        }
+        self.done |= self.game.game_over();
+
+        let info = StepInfo {
+            frame: self.frame,
+            stats: after,
+            lives_lost,
+            level_cleared,
+        };
+        (Observation::new(&self.game), reward, self.done, info)
+    }
+
+    pub fn game(&self) -> &Game<Sim<L>> {
+        &self.game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{instant_rules, MAP};
+    use crate::game::Direction;
+    use crate::sim::MemoryLoader;
+
+    fn new_env() -> PacmanEnv<MemoryLoader> {
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", MAP);
+        let params = Parameters {
+            start_dir: Direction::Left,
+            map_file: "map.txt".to_string(),
+            campaign: None,
+            rules: instant_rules(),
+        };
+        PacmanEnv::new(params, loader).unwrap()
+    }
+
+    #[test]
+    fn test_step_rewards_and_observation() {
+        let mut env = new_env();
+        let observation = env.reset(3).unwrap();
+        assert_eq!((observation.width, observation.height), (28, 10));
+        assert_eq!(observation.get(26, 8), Some(Cell::Player as u8));
+        assert_eq!(observation.get(25, 8), Some(Cell::Dot as u8));
+        assert_eq!(observation.get(0, 0), Some(Cell::Wall as u8));
+        assert_eq!(observation.get(28, 0), None);
+
+        // Pac-Man needs more than one tick to reach the next tile.
+        let (observation, reward, _, _) = env.step(Input::Left);
+        assert_eq!(observation.get(26, 8), Some(Cell::Player as u8));
+        assert_eq!(reward, 0.0);
+        let (observation, reward, done, info) = env.step(Input::None);
+        assert_eq!(observation.get(25, 8), Some(Cell::Player as u8));
+        assert_eq!(observation.get(26, 8), Some(Cell::Empty as u8));
+        assert_eq!(reward, 10.0);
+        assert!(!done);
+        assert_eq!((info.frame, info.stats.score), (2, 10));
+
+        let (_, reward, done, _) = env.step(Input::Quit);
+        assert_eq!(reward, 0.0);
+        assert!(done);
+        assert_eq!(env.reset(3).unwrap(), env.reset(4).unwrap());
+    }
+
+    #[test]
+    fn test_same_seed_same_episode() {
+        let mut env = new_env();
+        let mut play = |seed| {
+            env.reset(seed).unwrap();
+            (0..100)
+                .map(|i| env.step(ACTIONS[i % ACTIONS.len()]))
+                .map(|(observation, reward, done, _)| (observation, reward, done))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(play(7), play(7));
+    }
+}
@@ -1,27 +1,38 @@
+pub mod brain;
+pub mod campaign;
 pub mod ghost;
 pub mod map;
+pub mod rules;
+mod save_state;
+pub mod speed;
+pub mod tree;
 
 use std::convert::TryFrom;
+use std::hash::Hash;
 
-use crate::sim::Simulator;
+use crate::sim::{stable_hash, Fnv1a, Simulator};
 
+use self::brain::{GhostBrains, LoadedBrain};
+use self::campaign::{Campaign, Fruit, LevelSettings};
 use self::map::Map;
 use self::map::Tile;
+use self::rules::Rules;
+use self::speed::{Speeds, TILE};
 
 use self::ghost::{Ghost, GhostMode, Ghosts, Interaction};
 
 // const START_POS: (i32, i32) = ((map::MAP_WIDTH - 2) as i32, (map::MAP_HEIGHT - 2) as i32);
 
-// Parameters
-const SCORE_PELLET: u32 = 10;
-const SCORE_PU: u32 = 50;
-const SCORE_GHOST: u32 = 200;
-
 /// Constants that do not change while the game is running.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Parameters {
-    pub start_pos: (i32, i32),
     pub start_dir: Direction,
+    pub map_file: String,
+    /// Campaign file with the map and settings of every level, `map_file` is not used
+    /// when it is set.
+    pub campaign: Option<String>,
+    /// Rules of the game, campaign levels can override them.
+    pub rules: Rules,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -60,6 +71,7 @@ impl TryFrom<char> for Input {
             'r' => Ok(Input::Right),
             'q' => Ok(Input::Quit),
             'p' => Ok(Input::Pause),
+            'n' => Ok(Input::None),
             _ => Err(format!("Invalid input character: '{}'", c)),
         }
     }
@@ -67,21 +79,82 @@ impl TryFrom<char> for Input {
 
 pub struct Game<RG: Simulator> {
     params: Parameters,
+    campaign: Campaign,
+    campaign_hash: Option<u64>,
+    /// Hash of the behaviour trees of every level, `None` when no ghost has a tree.
+    trees_hash: Option<u64>,
+    /// Hash of the save state file the game was started from.
+    initial_state_hash: Option<u64>,
+    /// Map of every level in the campaign, before anything was eaten.
+    maps: Vec<Map>,
+    /// Brains of the ghosts of every level in the campaign.
+    brains: Vec<GhostBrains>,
     map: Map,
     lives: u8,
+    extra_life_awarded: bool,
     score: u32,
     level: usize,
+    phase: GamePhase,
+    /// Ticks left in the current phase, not used while playing or after game over.
+    phase_timer: u16,
     x: i32,
     y: i32,
     direction: Direction,
     direction_intent: Direction,
+    /// Progress towards the next tile, see [`TILE`].
+    pacman_progress: u32,
+    /// Pac-Man ate a dot or power up on his last move, which slows him down.
+    pacman_eating: bool,
     ghosts: Ghosts,
+    /// Bonus fruits put out in this level.
+    fruits_spawned: u8,
+    /// Ticks left before the bonus fruit disappears, 0 when there is none.
+    fruit_timer: u16,
     ticks: u32,
     paused: bool,
+    /// What happened during the last tick.
+    events: Vec<Event>,
     rg: RG,
 }
 
-#[derive(Copy, Clone, Debug)]
+/// What the game is doing, only while `Playing` do Pac-Man and the ghosts move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamePhase {
+    /// Waiting before the start of a level or a new life.
+    Ready,
+    Playing,
+    /// Pac-Man was caught, a new life starts when this ends.
+    Dying,
+    /// All pellets are eaten, the next level starts when this ends.
+    LevelClear,
+    /// No lives left, or the last level of a campaign that ends was cleared.
+    GameOver,
+}
+
+impl GamePhase {
+    fn as_str(self) -> &'static str {
+        match self {
+            GamePhase::Ready => "ready",
+            GamePhase::Playing => "playing",
+            GamePhase::Dying => "dying",
+            GamePhase::LevelClear => "level_clear",
+            GamePhase::GameOver => "game_over",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "ready" => Some(GamePhase::Ready),
+            "playing" => Some(GamePhase::Playing),
+            "dying" => Some(GamePhase::Dying),
+            "level_clear" => Some(GamePhase::LevelClear),
+            "game_over" => Some(GamePhase::GameOver),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash)]
 pub enum Direction {
     Up,
     Down,
@@ -100,6 +173,40 @@ impl Direction {
     }
 }
 
+impl From<Direction> for char {
+    fn from(val: Direction) -> Self {
+        match val {
+            Direction::Up => 'u',
+            Direction::Down => 'd',
+            Direction::Left => 'l',
+            Direction::Right => 'r',
+        }
+    }
+}
+
+impl TryFrom<char> for Direction {
+    type Error = String;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'u' => Ok(Direction::Up),
+            'd' => Ok(Direction::Down),
+            'l' => Ok(Direction::Left),
+            'r' => Ok(Direction::Right),
+            _ => Err(format!("Invalid direction character: '{}'", c)),
+        }
+    }
+}
+
+/// Something that happened during a tick, for the view to show. Events are not part of
+/// the game state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    GhostEaten { points: u32, x: i32, y: i32 },
+    FruitEaten { points: u32, x: i32, y: i32 },
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct Stats {
     pub lives: u8,
     pub score: u32,
@@ -107,25 +214,74 @@ pub struct Stats {
 }
 
 impl<RG: Simulator> Game<RG> {
-    pub fn new(params: Parameters, mut sim: RG) -> Self {
+    pub fn new(params: Parameters, mut sim: RG) -> Result<Self, String> {
         // let mut sim = RG::default();
-        let map_file = sim.load_file("map.txt");
-
-        Game {
-            params,
-            map: Map::new(map_file),
-            lives: 5,
+        let (campaign, campaign_hash) = match &params.campaign {
+            Some(filename) => {
+                let (campaign, hash) = Campaign::load(&mut sim, filename, &params.rules)?;
+                (campaign, Some(hash))
+            }
+            None => (Campaign::single_map(&params.map_file, &params.rules), None),
+        };
+        let mut maps = Vec::with_capacity(campaign.levels.len());
+        let mut brains = Vec::with_capacity(campaign.levels.len());
+        for level in &campaign.levels {
+            let map_file = sim.load_file(&level.map_file).map_err(|e| e.to_string())?;
+            let map = Map::parse(map_file)
+                .map_err(|e| format!("Invalid map '{}': {}", level.map_file, e))?;
+            maps.push(map);
+            brains.push(brain::load_brains(&mut sim, &level.rules)?);
+        }
+        // The maps of a campaign are files of their own that can change as well.
+        let layouts: Vec<u64> = maps.iter().map(Map::layout_hash).collect();
+        let campaign_hash = campaign_hash.map(|hash| stable_hash(&(hash, layouts)));
+        let trees: Vec<_> = brains
+            .iter()
+            .flatten()
+            .filter_map(|brain| match brain {
+                LoadedBrain::Tree(tree) => Some(tree),
+                LoadedBrain::Classic(_) => None,
+            })
+            .collect();
+        let trees_hash = if trees.is_empty() {
+            None
+        } else {
+            Some(stable_hash(&trees))
+        };
+        let map = maps[0].clone();
+
+        let (x, y) = map.player_start();
+        let mut game = Game {
+            ghosts: Ghosts::new(&map, &campaign.levels[0].rules, &brains[0], 1),
+            lives: campaign.levels[0].rules.lives,
+            extra_life_awarded: false,
+            phase: GamePhase::Ready,
+            phase_timer: 0,
+            map,
+            maps,
+            brains,
+            campaign,
+            campaign_hash,
+            trees_hash,
+            initial_state_hash: None,
             score: 0,
             level: 1,
-            x: params.start_pos.0,
-            y: params.start_pos.1,
+            x,
+            y,
             direction: params.start_dir,
             direction_intent: params.start_dir,
-            ghosts: Ghosts::new(),
+            pacman_progress: 0,
+            pacman_eating: false,
+            fruits_spawned: 0,
+            fruit_timer: 0,
             ticks: 0,
             paused: false,
+            events: Vec::new(),
             rg: sim,
-        }
+            params,
+        };
+        game.set_phase(GamePhase::Ready);
+        Ok(game)
     }
 
     pub fn input(&mut self, input: Input) -> bool {
@@ -139,12 +295,11 @@ impl<RG: Simulator> Game<RG> {
             Input::Pause => self.paused = !self.paused,
             Input::None => (),
         }
-        return should_quit;
+        should_quit
     }
 
     pub fn update(&mut self) {
         if !self.paused {
-            println!("score: {}", self.stats().score);
             self.tick();
         }
     }
@@ -170,7 +325,7 @@ impl<RG: Simulator> Game<RG> {
     }
 
     pub fn set_direction_intent(&mut self, direction: Direction) {
-        if self.lives == 0 {
+        if self.phase == GamePhase::GameOver {
             return;
         }
         self.direction_intent = direction;
@@ -181,32 +336,113 @@ impl<RG: Simulator> Game<RG> {
 
     pub fn tick(&mut self) {
         self.ticks += 1;
-        if self.map.pellets() == 0 {
-            self.advance_level();
+        self.events.clear();
+        if self.phase != GamePhase::Playing {
+            if self.phase_timer > 0 {
+                self.phase_timer -= 1;
+                if self.phase_timer == 0 {
+                    self.end_phase();
+                }
+            }
             return;
         }
-        if self.lives == 0 {
+        self.pacman_progress += self.pacman_speed();
+        while self.pacman_progress >= TILE {
+            self.pacman_progress -= TILE;
+            if !self.move_pacman() {
+                // Blocked by a wall, start from the middle of the tile once he can move.
+                self.pacman_progress = 0;
+            }
+        }
+        self.check_extra_life();
+        if self.map.pellets() == 0 {
+            self.set_phase(GamePhase::LevelClear);
             return;
         }
-        self.move_pacman();
+        self.update_fruit();
         self.ghosts
             .move_ghosts(&self.map, (self.x, self.y, self.direction), &mut self.rg);
 
         match self.ghosts.interact_with_player((self.x, self.y)) {
             Some(Interaction::KillPlayer) => {
-                self.x = self.params.start_pos.0;
-                self.y = self.params.start_pos.1;
-                // Do we also want to set start direction here?
-                self.lives -= 1;
+                // Rules set in code can start the game without lives.
+                self.lives = self.lives.saturating_sub(1);
+                self.fruit_timer = 0;
+                self.set_phase(GamePhase::Dying);
             }
-            Some(Interaction::KillGhost(n)) => {
-                self.score += SCORE_GHOST * n as u32;
+            Some(Interaction::KillGhosts(killed)) => {
+                for (points, (x, y)) in killed {
+                    self.add_score(points);
+                    self.events.push(Event::GhostEaten { points, x, y });
+                }
+                self.check_extra_life();
             }
             None => (),
         }
     }
 
-    fn move_pacman(&mut self) {
+    /// Enter `phase`, phases that last 0 ticks end right away.
+    fn set_phase(&mut self, phase: GamePhase) {
+        let rules = &self.level_settings().rules;
+        self.phase_timer = match phase {
+            GamePhase::Ready => rules.ready_ticks,
+            GamePhase::Dying => rules.dying_ticks,
+            GamePhase::LevelClear => rules.level_clear_ticks,
+            GamePhase::Playing | GamePhase::GameOver => 0,
+        };
+        self.phase = phase;
+        if self.phase_timer == 0 {
+            self.end_phase();
+        }
+    }
+
+    fn end_phase(&mut self) {
+        match self.phase {
+            GamePhase::Ready => self.phase = GamePhase::Playing,
+            GamePhase::Dying if self.lives == 0 => self.phase = GamePhase::GameOver,
+            GamePhase::Dying => {
+                // Do we also want to set start direction here?
+                (self.x, self.y) = self.map.player_start();
+                self.reset_pacman_speed();
+                self.ghosts.reset();
+                self.set_phase(GamePhase::Ready);
+            }
+            GamePhase::LevelClear => self.advance_level(),
+            GamePhase::Playing | GamePhase::GameOver => (),
+        }
+    }
+
+    /// Scores in the rules can be as high as the score itself, it stops at the highest.
+    fn add_score(&mut self, points: u32) {
+        self.score = self.score.saturating_add(points);
+    }
+
+    fn check_extra_life(&mut self) {
+        let threshold = self.level_settings().rules.extra_life_score;
+        if !self.extra_life_awarded && threshold > 0 && self.score >= threshold {
+            self.extra_life_awarded = true;
+            self.lives = self.lives.saturating_add(1);
+        }
+    }
+
+    /// Speed of Pac-Man for the level, the ghost mode and whether he is eating.
+    fn pacman_speed(&self) -> u32 {
+        let speeds = Speeds::for_level(self.level, &self.level_settings().rules);
+        match (self.frightened(), self.pacman_eating) {
+            (false, false) => speeds.pacman,
+            (false, true) => speeds.pacman_dots,
+            (true, false) => speeds.pacman_frightened,
+            (true, true) => speeds.pacman_frightened_dots,
+        }
+    }
+
+    fn reset_pacman_speed(&mut self) {
+        self.pacman_progress = 0;
+        self.pacman_eating = false;
+    }
+
+    /// Move Pac-Man one tile, returns false when a wall is in the way.
+    fn move_pacman(&mut self) -> bool {
         if self.can_turn() {
             self.direction = self.direction_intent;
         }
@@ -216,10 +452,12 @@ impl<RG: Simulator> Game<RG> {
             Direction::Left => (self.x - 1, self.y),
             Direction::Right => (self.x + 1, self.y),
         };
-        if !self.map.is_wall(x, y) {
-            self.x = x;
-            self.y = y;
+        if self.map.is_wall(x, y) {
+            return false;
         }
+        self.x = x;
+        self.y = y;
+        self.pacman_eating = false;
         match self.map.get(x, y) {
             None => {
                 if x == -1 {
@@ -231,15 +469,45 @@ impl<RG: Simulator> Game<RG> {
             Some(Tile::Empty) => (),
             Some(Tile::Dot) => {
                 self.map.consume(x, y);
-                self.score += SCORE_PELLET;
+                self.pacman_eating = true;
+                self.ghosts.dot_eaten();
+                self.add_score(self.level_settings().rules.score_pellet);
             }
             Some(Tile::PowerUp) => {
                 self.map.consume(x, y);
+                self.pacman_eating = true;
+                self.ghosts.dot_eaten();
                 self.ghosts.frighten();
-                self.score += SCORE_PU;
+                self.add_score(self.level_settings().rules.score_power_up);
             }
             _ => (),
         }
+        if self.fruit_timer > 0 && (self.x, self.y) == self.map.fruit_tile() {
+            let points = self.fruit(self.level).points();
+            self.add_score(points);
+            self.fruit_timer = 0;
+            self.events.push(Event::FruitEaten {
+                points,
+                x: self.x,
+                y: self.y,
+            });
+        }
+        true
+    }
+
+    /// Count down the bonus fruit, and put out the next one once enough pellets are eaten.
+    fn update_fruit(&mut self) {
+        let rules = &self.level_settings().rules;
+        let thresholds = [rules.first_fruit_pellets, rules.second_fruit_pellets];
+        let fruit_ticks = rules.fruit_ticks;
+        self.fruit_timer = self.fruit_timer.saturating_sub(1);
+        let eaten = self.maps[self.map_index(self.level)].pellets() - self.map.pellets();
+        if let Some(threshold) = thresholds.get(self.fruits_spawned as usize) {
+            if eaten >= *threshold {
+                self.fruits_spawned += 1;
+                self.fruit_timer = fruit_ticks;
+            }
+        }
     }
 
     fn can_turn(&self) -> bool {
@@ -249,37 +517,165 @@ impl<RG: Simulator> Game<RG> {
             Direction::Left => (self.x - 1, self.y),
             Direction::Right => (self.x + 1, self.y),
         };
-        match self.map.get(x, y) {
-            None => false,
-            Some(Tile::Wall) => false,
-            _ => true,
-        }
+        !matches!(self.map.get(x, y), None | Some(Tile::Wall))
     }
 
     fn advance_level(&mut self) {
         self.level += 1;
-        self.x = self.params.start_pos.0;
-        self.y = self.params.start_pos.1;
-        self.ghosts.reset();
-        self.map.reset();
+        if let Some(i) = self.campaign.level_index(self.level) {
+            self.map = self.maps[i].clone();
+            self.ghosts = Ghosts::new(
+                &self.map,
+                &self.campaign.levels[i].rules,
+                &self.brains[i],
+                self.level,
+            );
+            self.fruits_spawned = 0;
+            self.fruit_timer = 0;
+            (self.x, self.y) = self.map.player_start();
+            self.reset_pacman_speed();
+            self.set_phase(GamePhase::Ready);
+        } else {
+            self.phase = GamePhase::GameOver;
+        }
+    }
+
+    /// Index in the campaign of the map of `level`. After the last level of a campaign that
+    /// ends, that is the map of the last level.
+    fn map_index(&self, level: usize) -> usize {
+        self.campaign
+            .level_index(level)
+            .unwrap_or(self.campaign.levels.len() - 1)
+    }
+
+    /// True once the last level of a campaign that does not loop has been cleared.
+    pub fn finished(&self) -> bool {
+        self.campaign.level_index(self.level).is_none()
+    }
+
+    pub fn campaign(&self) -> &Campaign {
+        &self.campaign
+    }
+
+    /// Hash of the campaign file and the maps of its levels, `None` when playing a single
+    /// map.
+    pub fn campaign_hash(&self) -> Option<u64> {
+        self.campaign_hash
+    }
+
+    /// Hash of the behaviour trees the ghosts of every level were loaded with, `None` when
+    /// no ghost has a tree for a brain.
+    pub fn trees_hash(&self) -> Option<u64> {
+        self.trees_hash
+    }
+
+    /// Hash of the save state file loaded with [`Game::load_state_file`], `None` when the
+    /// game started from the beginning.
+    pub fn initial_state_hash(&self) -> Option<u64> {
+        self.initial_state_hash
+    }
+
+    /// Settings of the current level, or of the last level once the campaign has ended.
+    pub fn level_settings(&self) -> &LevelSettings {
+        &self.campaign.levels[self.map_index(self.level)]
+    }
+
+    pub fn fruit(&self, level: usize) -> Fruit {
+        self.campaign.fruit(level)
+    }
+
+    /// The bonus fruit and its tile while it is out.
+    pub fn bonus_fruit(&self) -> Option<(Fruit, (i32, i32))> {
+        if self.fruit_timer > 0 {
+            Some((self.fruit(self.level), self.map.fruit_tile()))
+        } else {
+            None
+        }
     }
 
     pub fn map(&self) -> &Map {
         &self.map
     }
 
+    pub fn params(&self) -> &Parameters {
+        &self.params
+    }
+
     pub fn player(&self) -> (i32, i32, Direction) {
         (self.x, self.y, self.direction_intent)
     }
 
+    /// Direction Pac-Man is moving in and his progress towards the next tile in that
+    /// direction, see [`TILE`].
+    pub fn player_progress(&self) -> (Direction, u32) {
+        (self.direction, self.pacman_progress)
+    }
+
     pub fn ghosts(&self) -> &[Ghost] {
-        &self.ghosts.get()
+        self.ghosts.get()
     }
 
     pub fn ghost_mode(&self) -> GhostMode {
         self.ghosts.ghost_mode()
     }
 
+    /// See [`Ghosts::house_counters`].
+    pub fn house_counters(&self) -> [Option<(u32, u32)>; 4] {
+        self.ghosts.house_counters()
+    }
+
+    /// See [`Ghosts::idle_ticks`].
+    pub fn idle_ticks(&self) -> (u16, u16) {
+        self.ghosts.idle_ticks()
+    }
+
+    pub fn phase(&self) -> GamePhase {
+        self.phase
+    }
+
+    /// True once the game has ended and ticks no longer change anything but the tick
+    /// counter.
+    pub fn game_over(&self) -> bool {
+        self.phase == GamePhase::GameOver
+    }
+
+    /// Events of the last tick.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Number of ticks since the start of the game.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Stable hash of the complete game state, used to detect when a replay diverges from
+    /// the original run.
+    pub fn state_hash(&self) -> u64 {
+        use std::hash::Hasher;
+
+        let mut hasher = Fnv1a::default();
+        self.map.hash(&mut hasher);
+        self.lives.hash(&mut hasher);
+        self.extra_life_awarded.hash(&mut hasher);
+        self.score.hash(&mut hasher);
+        self.level.hash(&mut hasher);
+        self.phase.hash(&mut hasher);
+        self.phase_timer.hash(&mut hasher);
+        (self.x, self.y).hash(&mut hasher);
+        self.direction.hash(&mut hasher);
+        self.direction_intent.hash(&mut hasher);
+        self.pacman_progress.hash(&mut hasher);
+        self.pacman_eating.hash(&mut hasher);
+        self.ghosts.hash(&mut hasher);
+        self.fruits_spawned.hash(&mut hasher);
+        self.fruit_timer.hash(&mut hasher);
+        self.ticks.hash(&mut hasher);
+        self.paused.hash(&mut hasher);
+        self.rg.state().hash(&mut hasher);
+        hasher.finish()
+    }
+
     pub fn stats(&self) -> Stats {
         Stats {
             lives: self.lives,
@@ -300,3 +696,152 @@ impl<RG: Simulator> Game<RG> {
 //         self.map.remove_all_pellets();
 //     }
 // }
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::{Direction, Game, Parameters, Rules};
+    use crate::sim::{MemoryLoader, Sim};
+
+    pub const MAP: &str = "\
+############################
+#................X.........#
+#..........................#
+#..........HCPH............#
+#..........HIBH............#
+#..............X.X.........#
+#..........................#
+#..........................#
+#.........................S#
+############################";
+
+    /// Rules without the pauses between phases, so tests can play right away.
+    pub fn instant_rules() -> Rules {
+        Rules {
+            ready_ticks: 0,
+            dying_ticks: 0,
+            level_clear_ticks: 0,
+            ..Rules::default()
+        }
+    }
+
+    /// A game on `MAP` that does not touch the disk.
+    pub fn new_game(seed: u64) -> Game<Sim<MemoryLoader>> {
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", MAP);
+        let params = Parameters {
+            start_dir: Direction::Left,
+            map_file: "map.txt".to_string(),
+            campaign: None,
+            rules: Rules::default(),
+        };
+        Game::new(params, Sim::new(seed, loader)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{instant_rules, new_game, MAP};
+    use super::*;
+    use crate::sim::{MemoryLoader, Sim};
+
+    fn fruit_game() -> Game<Sim<MemoryLoader>> {
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", MAP);
+        let params = Parameters {
+            start_dir: Direction::Left,
+            map_file: "map.txt".to_string(),
+            campaign: None,
+            rules: Rules {
+                first_fruit_pellets: 1,
+                second_fruit_pellets: 3,
+                fruit_ticks: 2,
+                ..instant_rules()
+            },
+        };
+        Game::new(params, Sim::new(1, loader)).unwrap()
+    }
+
+    /// Tick until Pac-Man is on the next tile.
+    fn tick_until_moved<RG: Simulator>(game: &mut Game<RG>) {
+        let pos = (game.x, game.y);
+        while (game.x, game.y) == pos {
+            game.tick();
+        }
+    }
+
+    #[test]
+    fn test_bonus_fruit() {
+        // Without a fruit tile in the map the fruit appears where Pac-Man starts.
+        let mut game = fruit_game();
+        tick_until_moved(&mut game);
+        assert_eq!(game.bonus_fruit(), Some((Fruit::Cherry, (26, 8))));
+
+        game.input(Input::Right);
+        tick_until_moved(&mut game);
+        assert_eq!(game.bonus_fruit(), None);
+        assert_eq!(game.stats().score, 10 + 100);
+        assert_eq!(
+            game.events(),
+            [Event::FruitEaten {
+                points: 100,
+                x: 26,
+                y: 8
+            }]
+        );
+
+        // The second fruit goes away when it is not eaten in time.
+        game.input(Input::Left);
+        while game.bonus_fruit().is_none() {
+            game.tick();
+        }
+        assert_eq!(game.stats().score, 30 + 100);
+        game.tick();
+        game.tick();
+        assert_eq!(game.bonus_fruit(), None);
+    }
+
+    #[test]
+    fn test_phases() {
+        let mut game = new_game(1);
+        assert_eq!(game.phase(), GamePhase::Ready);
+        for _ in 0..game.level_settings().rules.ready_ticks {
+            assert_eq!((game.x, game.y), (26, 8));
+            game.tick();
+        }
+        assert_eq!(game.phase(), GamePhase::Playing);
+
+        game.score = 9995;
+        tick_until_moved(&mut game);
+        assert_eq!(game.stats().lives, 6);
+        game.score = 19995;
+        tick_until_moved(&mut game);
+        assert_eq!(game.stats().lives, 6);
+        game.score = u32::MAX - 5;
+        tick_until_moved(&mut game);
+        assert_eq!(game.stats().score, u32::MAX);
+
+        // Wait for the ghosts to catch Pac-Man on the last life.
+        game.lives = 1;
+        while game.phase() == GamePhase::Playing {
+            game.tick();
+        }
+        assert_eq!(game.phase(), GamePhase::Dying);
+        assert_eq!(game.stats().lives, 0);
+        for _ in 0..game.level_settings().rules.dying_ticks {
+            game.tick();
+        }
+        assert!(game.game_over());
+        let (x, y) = (game.x, game.y);
+        game.tick();
+        assert!(game.game_over());
+        assert_eq!((game.x, game.y), (x, y));
+
+        // A game started without lives is over at the first catch.
+        let mut game = new_game(1);
+        game.lives = 0;
+        while !game.game_over() {
+            game.tick();
+        }
+        assert_eq!(game.stats().lives, 0);
+    }
+}
@@ -0,0 +1,289 @@
+//! Ghost brains: what a ghost outside the house does next.
+//!
+//! Leaving the house, returning as eyes and turning around on mode switches are the same
+//! for every ghost, a [`GhostBrain`] only decides where a ghost goes from there. Which
+//! brain drives which ghost comes from the rules, see [`Rules::brains`](super::rules::Rules).
+
+use std::fmt;
+use std::rc::Rc;
+
+use super::ghost::{Ghost, GhostMode, Name};
+use super::map::Map;
+use super::rules::Rules;
+use super::tree::Tree;
+use super::Direction;
+use crate::sim::{FileLoader, RandGen};
+
+/// What a ghost sees when it picks its next move.
+pub struct GhostView<'a> {
+    pub map: &'a Map,
+    /// Position and direction of Pac-Man.
+    pub player: (i32, i32, Direction),
+    /// All four ghosts, in the order Blinky, Pinky, Inky, Clyde.
+    pub ghosts: &'a [Ghost],
+    /// Index in `ghosts` of the ghost that moves.
+    pub me: usize,
+    /// The mode this ghost is in, Blinky keeps chasing during scatter as Cruise Elroy.
+    pub mode: GhostMode,
+}
+
+impl<'a> GhostView<'a> {
+    pub fn ghost(&self) -> &Ghost {
+        &self.ghosts[self.me]
+    }
+
+    pub fn player_pos(&self) -> (i32, i32) {
+        (self.player.0, self.player.1)
+    }
+
+    /// The corner of the ghost that moves.
+    pub fn scatter_target(&self) -> (i32, i32) {
+        self.map.scatter_targets()[self.me]
+    }
+
+    pub fn find(&self, name: Name) -> &Ghost {
+        &self.ghosts[name as usize]
+    }
+}
+
+/// A move picked by a brain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    /// Take the open tile closest to the target, without turning around.
+    Towards((i32, i32)),
+    /// Take a random open tile, without turning around.
+    Flee,
+    /// Take a random neighbouring tile inside the house, stay put when there is none.
+    HouseMove,
+}
+
+pub trait GhostBrain {
+    /// The next move, random choices must come from `rg` so games can be replayed.
+    fn next_move(&self, view: &GhostView, rg: &mut dyn RandGen) -> Move;
+}
+
+/// The brains of the arcade game, named after the ghost that has them there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BrainKind {
+    Blinky,
+    Pinky,
+    Inky,
+    Clyde,
+}
+
+impl BrainKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BrainKind::Blinky => "blinky",
+            BrainKind::Pinky => "pinky",
+            BrainKind::Inky => "inky",
+            BrainKind::Clyde => "clyde",
+        }
+    }
+
+    pub(crate) fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "blinky" => Some(BrainKind::Blinky),
+            "pinky" => Some(BrainKind::Pinky),
+            "inky" => Some(BrainKind::Inky),
+            "clyde" => Some(BrainKind::Clyde),
+            _ => None,
+        }
+    }
+
+    pub fn brain(self) -> &'static dyn GhostBrain {
+        match self {
+            BrainKind::Blinky => &BlinkyBrain,
+            BrainKind::Pinky => &PinkyBrain,
+            BrainKind::Inky => &InkyBrain,
+            BrainKind::Clyde => &ClydeBrain,
+        }
+    }
+}
+
+/// The brain of a ghost as set in the rules.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Brain {
+    Classic(BrainKind),
+    /// A behaviour tree from this file, see [`tree`](super::tree).
+    Tree(String),
+}
+
+impl Brain {
+    /// A classic brain by name, or `tree:<file>`.
+    pub(crate) fn from_str(s: &str) -> Option<Self> {
+        match s.strip_prefix("tree:") {
+            Some("") => None,
+            Some(filename) => Some(Brain::Tree(filename.to_string())),
+            None => BrainKind::from_str(s).map(Brain::Classic),
+        }
+    }
+}
+
+impl Brain {
+    /// The brain ready to drive a ghost, with its tree loaded from `loader`.
+    pub fn load<L: FileLoader>(&self, loader: &mut L) -> Result<LoadedBrain, String> {
+        Ok(match self {
+            Brain::Classic(kind) => LoadedBrain::Classic(*kind),
+            Brain::Tree(filename) => LoadedBrain::Tree(Rc::new(Tree::load(loader, filename)?)),
+        })
+    }
+}
+
+/// A brain as set in the rules, with its tree loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LoadedBrain {
+    Classic(BrainKind),
+    Tree(Rc<Tree>),
+}
+
+impl LoadedBrain {
+    pub fn brain(&self) -> &dyn GhostBrain {
+        match self {
+            LoadedBrain::Classic(kind) => kind.brain(),
+            LoadedBrain::Tree(tree) => tree.as_ref(),
+        }
+    }
+}
+
+/// The brain of every ghost, in the order Blinky, Pinky, Inky, Clyde.
+pub type GhostBrains = [LoadedBrain; 4];
+
+/// Load the brains of the ghosts in `rules`.
+pub fn load_brains<L: FileLoader>(loader: &mut L, rules: &Rules) -> Result<GhostBrains, String> {
+    Ok([
+        rules.brains[0].load(loader)?,
+        rules.brains[1].load(loader)?,
+        rules.brains[2].load(loader)?,
+        rules.brains[3].load(loader)?,
+    ])
+}
+
+impl fmt::Display for Brain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Brain::Classic(kind) => write!(f, "{}", kind.as_str()),
+            Brain::Tree(filename) => write!(f, "tree:{}", filename),
+        }
+    }
+}
+
+/// Scatter to the ghost's corner, chase `target` and flee while frightened.
+fn classic_move(view: &GhostView, target: (i32, i32)) -> Move {
+    match view.mode {
+        GhostMode::Frightened => Move::Flee,
+        GhostMode::Scatter => Move::Towards(view.scatter_target()),
+        GhostMode::Chase => Move::Towards(target),
+    }
+}
+
+/// Chases Pac-Man himself.
+pub struct BlinkyBrain;
+
+impl GhostBrain for BlinkyBrain {
+    fn next_move(&self, view: &GhostView, _rg: &mut dyn RandGen) -> Move {
+        classic_move(view, view.player_pos())
+    }
+}
+
+/// Aims four tiles ahead of Pac-Man.
+pub struct PinkyBrain;
+
+impl GhostBrain for PinkyBrain {
+    fn next_move(&self, view: &GhostView, _rg: &mut dyn RandGen) -> Move {
+        classic_move(view, calc_pinky_target(view.player))
+    }
+}
+
+/// Aims at the tile two ahead of Pac-Man, mirrored around Blinky.
+pub struct InkyBrain;
+
+impl GhostBrain for InkyBrain {
+    fn next_move(&self, view: &GhostView, _rg: &mut dyn RandGen) -> Move {
+        let blinky = view.find(Name::Blinky);
+        classic_move(
+            view,
+            calc_inky_target((blinky.x(), blinky.y()), view.player),
+        )
+    }
+}
+
+/// Chases Pac-Man from afar, but goes back to his corner when close.
+pub struct ClydeBrain;
+
+impl GhostBrain for ClydeBrain {
+    fn next_move(&self, view: &GhostView, _rg: &mut dyn RandGen) -> Move {
+        let me = view.ghost();
+        let target = calc_clyde_target((me.x(), me.y()), view.player_pos(), view.scatter_target());
+        classic_move(view, target)
+    }
+}
+
+fn calc_pinky_target(player: (i32, i32, Direction)) -> (i32, i32) {
+    let v = player.2.to_vector();
+    let plr = (player.0, player.1);
+    (plr.0 + v.0 * 4, plr.1 + v.1 * 4)
+}
+
+fn calc_inky_target(blinky: (i32, i32), player: (i32, i32, Direction)) -> (i32, i32) {
+    let plr = (player.0, player.1);
+    let mid_tgt = {
+        let v = player.2.to_vector();
+        (plr.0 + v.0 * 2, plr.1 + v.1 * 2)
+    };
+    let tgt_vec = ((mid_tgt.0 - blinky.0) * 2, (mid_tgt.1 - blinky.1) * 2);
+    (blinky.0 + tgt_vec.0, blinky.1 + tgt_vec.1)
+}
+
+fn calc_clyde_target(clyde: (i32, i32), plr: (i32, i32), home: (i32, i32)) -> (i32, i32) {
+    if (((clyde.0 - plr.0).pow(2) + (clyde.1 - plr.1).pow(2)) as f64).sqrt() < 8.0 {
+        home
+    } else {
+        plr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::new_game;
+
+    #[test]
+    fn test_classic_brains() {
+        let mut game = new_game(1);
+        let map = game.map().clone();
+        let ghosts = game.ghosts().to_vec();
+        let view = |me, mode, player| GhostView {
+            map: &map,
+            player,
+            ghosts: &ghosts,
+            me,
+            mode,
+        };
+        let far = (1, 1, Direction::Right);
+        let mut next =
+            |kind: BrainKind, view: GhostView| kind.brain().next_move(&view, &mut game.rg);
+
+        let chase = view(1, GhostMode::Chase, far);
+        assert_eq!(next(BrainKind::Blinky, chase), Move::Towards((1, 1)));
+        let chase = view(1, GhostMode::Chase, far);
+        assert_eq!(next(BrainKind::Pinky, chase), Move::Towards((5, 1)));
+        let scatter = view(1, GhostMode::Scatter, far);
+        assert_eq!(
+            next(BrainKind::Pinky, scatter),
+            Move::Towards(map.scatter_targets()[1])
+        );
+        let frightened = view(2, GhostMode::Frightened, far);
+        assert_eq!(next(BrainKind::Inky, frightened), Move::Flee);
+
+        // Clyde keeps his distance, also when another ghost has his brain.
+        let near = (ghosts[0].x() + 1, ghosts[0].y(), Direction::Left);
+        let chase = view(0, GhostMode::Chase, near);
+        assert_eq!(
+            next(BrainKind::Clyde, chase),
+            Move::Towards(map.scatter_targets()[0])
+        );
+        let chase = view(0, GhostMode::Chase, far);
+        assert_eq!(next(BrainKind::Clyde, chase), Move::Towards((1, 1)));
+    }
+}
@@ -0,0 +1,341 @@
+//! Campaigns: the list of levels to play, each with its own map and settings.
+//!
+//! A campaign file has one level per line, followed by its settings:
+//!
+//! ```text
+//! # Lines starting with '#' are comments.
+//! after_last=loop
+//! level map=maps/classic.txt fruit=cherry
+//! level map=map.txt fruit=orange ghost_speed=110 frightened_ticks=20
+//! ```
+//!
+//! `after_last` is `loop` to start over from the first level after the last one, or `end`
+//! to finish the game there. Any of the [`Rules`] can be set for a level, the others come
+//! from the rules the game was started with.
+
+use super::rules::Rules;
+use crate::sim::{stable_hash, FileLoader};
+
+/// The bonus fruit of a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fruit {
+    Cherry,
+    Strawberry,
+    Orange,
+    Apple,
+    Melon,
+    Flower,
+    Bell,
+    Key,
+}
+
+impl Fruit {
+    pub const ALL: [Fruit; 8] = [
+        Fruit::Cherry,
+        Fruit::Strawberry,
+        Fruit::Orange,
+        Fruit::Apple,
+        Fruit::Melon,
+        Fruit::Flower,
+        Fruit::Bell,
+        Fruit::Key,
+    ];
+
+    /// The fruit of `level` in the arcade game.
+    pub fn for_level(level: usize) -> Fruit {
+        match level {
+            0 | 1 => Fruit::Cherry,
+            2 => Fruit::Strawberry,
+            3..=12 => Fruit::ALL[level.div_ceil(2)],
+            _ => Fruit::Key,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Fruit::Cherry => "cherry",
+            Fruit::Strawberry => "strawberry",
+            Fruit::Orange => "orange",
+            Fruit::Apple => "apple",
+            Fruit::Melon => "melon",
+            Fruit::Flower => "flower",
+            Fruit::Bell => "bell",
+            Fruit::Key => "key",
+        }
+    }
+
+    /// Points for eating the fruit.
+    pub fn points(self) -> u32 {
+        match self {
+            Fruit::Cherry => 100,
+            Fruit::Strawberry => 300,
+            Fruit::Orange => 500,
+            Fruit::Apple => 700,
+            Fruit::Melon => 1000,
+            Fruit::Flower => 2000,
+            Fruit::Bell => 3000,
+            Fruit::Key => 5000,
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Fruit> {
+        Fruit::ALL.iter().copied().find(|f| f.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelSettings {
+    pub map_file: String,
+    pub rules: Rules,
+    /// The fruit of the level, the arcade fruit for the level number if not set.
+    pub fruit: Option<Fruit>,
+}
+
+impl LevelSettings {
+    pub fn new(map_file: &str, rules: &Rules) -> Self {
+        LevelSettings {
+            map_file: map_file.to_string(),
+            rules: rules.clone(),
+            fruit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AfterLast {
+    Loop,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Campaign {
+    pub levels: Vec<LevelSettings>,
+    pub after_last: AfterLast,
+}
+
+impl Campaign {
+    /// Play `map_file` over and over, like a game without a campaign file.
+    pub fn single_map(map_file: &str, rules: &Rules) -> Self {
+        Campaign {
+            levels: vec![LevelSettings::new(map_file, rules)],
+            after_last: AfterLast::Loop,
+        }
+    }
+
+    /// Load a campaign file, returns it together with a hash of its contents. Levels start
+    /// from `rules`.
+    pub fn load<L: FileLoader>(
+        loader: &mut L,
+        filename: &str,
+        rules: &Rules,
+    ) -> Result<(Self, u64), String> {
+        let rows = loader.load_file(filename).map_err(|e| e.to_string())?;
+        let campaign = Campaign::parse(&rows, rules)
+            .map_err(|e| format!("Invalid campaign '{}': {}", filename, e))?;
+        Ok((campaign, stable_hash(&rows)))
+    }
+
+    pub fn parse(rows: &[Vec<char>], rules: &Rules) -> Result<Self, String> {
+        let mut levels = Vec::new();
+        let mut after_last = AfterLast::Loop;
+        for (i, row) in rows.iter().enumerate() {
+            let line: String = row.iter().collect();
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("Line {}: {}", i + 1, msg);
+            if let Some(value) = line.strip_prefix("after_last=") {
+                after_last = match value {
+                    "loop" => AfterLast::Loop,
+                    "end" => AfterLast::End,
+                    _ => return Err(err(format!("Expected 'loop' or 'end', got '{}'.", value))),
+                };
+            } else if let Some(settings) = line.strip_prefix("level ") {
+                levels.push(parse_level(settings, rules).map_err(err)?);
+            } else {
+                return Err(err(
+                    "Expected 'level key=value ...' or 'after_last=loop|end'.".to_string(),
+                ));
+            }
+        }
+        if levels.is_empty() {
+            return Err("Campaign has no levels.".to_string());
+        }
+        Ok(Campaign { levels, after_last })
+    }
+
+    /// Index in `levels` of level number `level`, counting from 1. `None` once the
+    /// campaign has ended.
+    pub fn level_index(&self, level: usize) -> Option<usize> {
+        let i = level.saturating_sub(1);
+        match self.after_last {
+            _ if i < self.levels.len() => Some(i),
+            AfterLast::Loop => Some(i % self.levels.len()),
+            AfterLast::End => None,
+        }
+    }
+
+    pub fn fruit(&self, level: usize) -> Fruit {
+        self.level_index(level)
+            .and_then(|i| self.levels[i].fruit)
+            .unwrap_or_else(|| Fruit::for_level(level))
+    }
+}
+
+fn parse_level(settings: &str, rules: &Rules) -> Result<LevelSettings, String> {
+    let mut level = LevelSettings::new("", rules);
+    for setting in settings.split_whitespace() {
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("Expected 'key=value', got '{}'.", setting))?;
+        let bad_value = || format!("Invalid value '{}' for '{}'.", value, key);
+        match key {
+            "map" => level.map_file = value.to_string(),
+            "fruit" => level.fruit = Some(Fruit::from_str(value).ok_or_else(bad_value)?),
+            _ => level.rules.set(key, value)?,
+        }
+    }
+    if level.map_file.is_empty() {
+        return Err("Level has no 'map'.".to_string());
+    }
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{instant_rules, MAP};
+    use crate::game::{Direction, Game, Parameters};
+    use crate::sim::{MemoryLoader, Sim};
+
+    fn rows(lines: &[&str]) -> Vec<Vec<char>> {
+        lines.iter().map(|line| line.chars().collect()).collect()
+    }
+
+    #[test]
+    fn test_parse_campaign() {
+        let base = Rules {
+            lives: 3,
+            ..Rules::default()
+        };
+        let campaign = Campaign::parse(
+            &rows(&[
+                "# The first two levels.",
+                "after_last=end",
+                "level map=a.txt",
+                "",
+                "level map=b.txt fruit=bell ghost_speed=120 frightened_ticks=10",
+            ]),
+            &base,
+        )
+        .unwrap();
+        assert_eq!(campaign.after_last, AfterLast::End);
+        assert_eq!(campaign.levels[0], LevelSettings::new("a.txt", &base));
+        assert_eq!(
+            campaign.levels[1],
+            LevelSettings {
+                map_file: "b.txt".to_string(),
+                rules: Rules {
+                    ghost_speed: 120,
+                    frightened_ticks: 10,
+                    ..base.clone()
+                },
+                fruit: Some(Fruit::Bell),
+            }
+        );
+        assert_eq!(campaign.level_index(2), Some(1));
+        assert_eq!(campaign.level_index(3), None);
+        assert_eq!(campaign.fruit(1), Fruit::Cherry);
+        assert_eq!(campaign.fruit(2), Fruit::Bell);
+
+        let looping = Campaign::single_map("a.txt", &base);
+        assert_eq!(looping.level_index(5), Some(0));
+        assert_eq!(looping.fruit(5), Fruit::Apple);
+
+        let err = |lines: &[&str]| Campaign::parse(&rows(lines), &base).unwrap_err();
+        assert_eq!(err(&["# nothing"]), "Campaign has no levels.");
+        assert_eq!(
+            err(&["level map=a.txt", "level fruit=key"]),
+            "Line 2: Level has no 'map'."
+        );
+        assert_eq!(
+            err(&["level map=a.txt ghost_speed=fast"]),
+            "Line 1: Invalid value 'fast' for 'ghost_speed'."
+        );
+        assert_eq!(
+            err(&["level map=a.txt speed=3"]),
+            "Line 1: Unknown rule 'speed'."
+        );
+        assert_eq!(
+            err(&["after_last=never"]),
+            "Line 1: Expected 'loop' or 'end', got 'never'."
+        );
+    }
+
+    #[test]
+    fn test_arcade_fruits() {
+        let fruits: Vec<_> = (1..=14).map(Fruit::for_level).collect();
+        use Fruit::*;
+        assert_eq!(
+            fruits,
+            [
+                Cherry, Strawberry, Orange, Orange, Apple, Apple, Melon, Melon, Flower, Flower,
+                Bell, Bell, Key, Key
+            ]
+        );
+    }
+
+    fn campaign_game() -> Game<Sim<MemoryLoader>> {
+        campaign_game_with(&MAP.replace("X.X", "..."))
+    }
+
+    /// The campaign game with `second_map` as the map of its second level.
+    fn campaign_game_with(second_map: &str) -> Game<Sim<MemoryLoader>> {
+        let mut loader = MemoryLoader::default();
+        loader.insert("a.txt", MAP);
+        loader.insert("b.txt", second_map);
+        loader.insert(
+            "campaign.txt",
+            "after_last=end\nlevel map=a.txt\nlevel map=b.txt ghost_speed=200",
+        );
+        let params = Parameters {
+            start_dir: Direction::Left,
+            map_file: "unused.txt".to_string(),
+            campaign: Some("campaign.txt".to_string()),
+            rules: instant_rules(),
+        };
+        Game::new(params, Sim::new(1, loader)).unwrap()
+    }
+
+    #[test]
+    fn test_game_plays_through_campaign() {
+        let mut game = campaign_game();
+        assert!(game.campaign_hash().is_some());
+        // Changing the map of a later level changes the hash of the campaign.
+        assert_ne!(
+            campaign_game_with(MAP).campaign_hash(),
+            game.campaign_hash()
+        );
+        let first_map = game.map().layout_hash();
+
+        game.map.remove_all_pellets();
+        game.tick();
+        assert_eq!(game.stats().level, 2);
+        assert_ne!(game.map().layout_hash(), first_map);
+        assert_eq!(game.level_settings().rules.ghost_speed, 200);
+
+        // Save states remember which level, and so which map, they were made on.
+        game.tick();
+        let mut other = campaign_game();
+        other.load_state(&game.save_state()).unwrap();
+        assert_eq!(other.state_hash(), game.state_hash());
+
+        game.map.remove_all_pellets();
+        game.tick();
+        assert!(game.finished());
+        game.tick();
+        assert_eq!(game.stats().level, 3);
+        assert_eq!(game.map().layout_hash(), other.map().layout_hash());
+    }
+}
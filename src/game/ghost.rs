@@ -1,27 +1,76 @@
+use super::brain::{GhostBrains, GhostView, Move};
 use super::map::Map;
+use super::rules::Rules;
+use super::save_state::{StateReader, StateWriter};
+use super::speed::{Speeds, TILE};
 use super::Direction;
 use crate::sim::RandGen;
 
-// const BLINKY_HOME: (i32, i32) = (map::MAP_WIDTH as i32 - 3, -2);
-// const PINKY_HOME: (i32, i32) = (2, -2);
-// const INKY_HOME: (i32, i32) = (map::MAP_WIDTH as i32 - 1, map::MAP_HEIGHT as i32);
-// const CLYDE_HOME: (i32, i32) = (0, map::MAP_HEIGHT as i32);
+/// Dots of the global counter, used after Pac-Man loses a life, at which each ghost
+/// leaves the house. Clyde's limit switches the ghosts back to their own counters.
+const GLOBAL_DOT_LIMITS: [u32; 4] = [0, 7, 17, 32];
 
-const BLINKY_HOME: (i32, i32) = (2, 2);
-const PINKY_HOME: (i32, i32) = (2, 2);
-const INKY_HOME: (i32, i32) = (2, 2);
-const CLYDE_HOME: (i32, i32) = (2, 2);
-const FRIGHTNED_TIMER: u16 = 30;
-const GHOST_MODE_TIMER: u16 = 7 * 4;
+/// Pellets left in a level of the arcade game when Blinky becomes Cruise Elroy, he speeds
+/// up again at half of them.
+fn arcade_elroy_pellets(level: usize) -> u32 {
+    match level {
+        0 | 1 => 20,
+        2 => 30,
+        3..=5 => 40,
+        6..=8 => 50,
+        9..=11 => 60,
+        12..=14 => 80,
+        15..=18 => 100,
+        _ => 120,
+    }
+}
+
+/// Lengths of the scatter and chase phases of the arcade game in milliseconds, starting
+/// with scatter. After the last phase the ghosts chase until the end of the level.
+fn arcade_schedule(level: usize) -> [u32; 7] {
+    match level {
+        0 | 1 => [7000, 20000, 7000, 20000, 5000, 20000, 5000],
+        2..=4 => [7000, 20000, 7000, 20000, 5000, 1_033_000, 17],
+        _ => [5000, 20000, 5000, 20000, 5000, 1_037_000, 17],
+    }
+}
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+/// The arcade schedule of `level` in ticks, every phase lasts at least one tick.
+fn mode_schedule(level: usize, ticks_per_second: u32) -> [u32; 7] {
+    let mut schedule = arcade_schedule(level);
+    for ticks in schedule.iter_mut() {
+        *ticks = ((u64::from(*ticks) * u64::from(ticks_per_second) + 500) / 1000).max(1) as u32;
+    }
+    schedule
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum GhostMode {
     Chase,
     Scatter,
     Frightened,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl GhostMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            GhostMode::Chase => "chase",
+            GhostMode::Scatter => "scatter",
+            GhostMode::Frightened => "frightened",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "chase" => Some(GhostMode::Chase),
+            "scatter" => Some(GhostMode::Scatter),
+            "frightened" => Some(GhostMode::Frightened),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash)]
 pub enum Name {
     Blinky,
     Pinky,
@@ -31,31 +80,86 @@ pub enum Name {
 
 pub enum Interaction {
     KillPlayer,
-    KillGhost(u8),
+    /// The points for and position of every ghost eaten.
+    KillGhosts(Vec<(u32, (i32, i32))>),
 }
 
+#[derive(Hash)]
 pub struct Ghosts {
     ghosts: [Ghost; 4],
+    starts: [(i32, i32); 4],
+    /// Where eaten ghosts come back.
+    respawn: (i32, i32),
+    rules: Rules,
+    brains: GhostBrains,
+    speeds: Speeds,
+    /// Ticks of every scatter and chase phase in this level.
+    schedule: [u32; 7],
+    /// Pellets left when Blinky becomes Cruise Elroy, see [`Ghosts::elroy`].
+    elroy_pellets: u32,
+    /// Cruise Elroy waits for Clyde to leave the house after Pac-Man lost a life.
+    elroy_paused: bool,
     ghost_mode: GhostMode,
-    mode_timer: u16,
+    /// Index in `schedule` of the current phase, also while frightened.
+    mode_phase: usize,
+    mode_timer: u32,
     frightened_timer: u16,
-    num_scatters: u8,
+    /// Ghosts eaten since the last power up, every one is worth double the one before.
+    eat_combo: u8,
+    /// Dots counted by every ghost towards leaving the house.
+    dot_counters: [u32; 4],
+    /// Dots eaten since Pac-Man lost a life, replaces the ghosts' own counters while set.
+    global_dots: Option<u32>,
+    /// Ticks since Pac-Man last ate a dot.
+    idle_ticks: u16,
 }
 
 impl Ghosts {
-    pub fn new() -> Self {
-        let (x, y) = (13, 4); // TODO get this from the map.
+    /// `brains` are the brains of `rules`, loaded with [`load_brains`](super::brain::load_brains).
+    pub fn new(map: &Map, rules: &Rules, brains: &GhostBrains, level: usize) -> Self {
+        Ghosts::from_starts(
+            map.ghost_starts(),
+            map.house_respawn(),
+            rules.clone(),
+            brains.clone(),
+            Speeds::for_level(level, rules),
+            mode_schedule(level, rules.ticks_per_second),
+            arcade_elroy_pellets(level) * rules.elroy_pellets / 100,
+        )
+    }
+
+    fn from_starts(
+        starts: [(i32, i32); 4],
+        respawn: (i32, i32),
+        rules: Rules,
+        brains: GhostBrains,
+        speeds: Speeds,
+        schedule: [u32; 7],
+        elroy_pellets: u32,
+    ) -> Self {
         Ghosts {
             ghosts: [
-                Ghost::new(Name::Blinky, (x, y), 2),
-                Ghost::new(Name::Pinky, (x, y - 1), 10),
-                Ghost::new(Name::Inky, (x - 1, y), 20),
-                Ghost::new(Name::Clyde, (x - 1, y - 1), 30),
+                Ghost::new(Name::Blinky, starts[0], true),
+                Ghost::new(Name::Pinky, starts[1], true),
+                Ghost::new(Name::Inky, starts[2], true),
+                Ghost::new(Name::Clyde, starts[3], true),
             ],
-            ghost_mode: GhostMode::Chase,
-            mode_timer: 0,
+            starts,
+            respawn,
+            rules,
+            brains,
+            speeds,
+            schedule,
+            elroy_pellets,
+            elroy_paused: false,
+            ghost_mode: GhostMode::Scatter,
+            mode_phase: 0,
+            mode_timer: schedule[0],
             frightened_timer: 0,
-            num_scatters: 2,
+            eat_combo: 0,
+            dot_counters: [0; 4],
+            global_dots: None,
+            idle_ticks: 0,
         }
     }
 
@@ -67,9 +171,32 @@ impl Ghosts {
         self.ghost_mode
     }
 
+    /// Frighten every ghost that is out, ghosts in the house or on their way back to it
+    /// stay as they are.
     pub fn frighten(&mut self) {
         self.ghost_mode = GhostMode::Frightened;
-        self.frightened_timer = FRIGHTNED_TIMER;
+        self.frightened_timer = self.rules.frightened_ticks;
+        self.eat_combo = 0;
+        for g in self.ghosts.iter_mut().filter(|g| !g.in_house && !g.eaten) {
+            g.frightened = true;
+        }
+        self.reverse();
+    }
+
+    /// Mode of the current phase of the schedule, even phases scatter.
+    fn scheduled_mode(&self) -> GhostMode {
+        if self.mode_phase.is_multiple_of(2) {
+            GhostMode::Scatter
+        } else {
+            GhostMode::Chase
+        }
+    }
+
+    /// Make every ghost outside the house turn around on its next move.
+    fn reverse(&mut self) {
+        for g in self.ghosts.iter_mut().filter(|g| !g.in_house && !g.eaten) {
+            g.reverse = true;
+        }
     }
 
     pub fn move_ghosts<RG: RandGen>(
@@ -78,96 +205,341 @@ impl Ghosts {
         player: (i32, i32, Direction),
         rg: &mut RG,
     ) {
-        let blinky = self.ghosts[0].pos;
-        for g in self.ghosts.iter_mut() {
-            if g.house_timer != 0 {
-                g.house_move(map, rg);
-                continue;
-            }
-            let plr = (player.0, player.1);
-            match self.ghost_mode {
-                GhostMode::Frightened => g.flee(map, rg),
-                GhostMode::Chase => {
-                    let target = match g.name {
-                        Name::Blinky => plr,
-                        Name::Pinky => calc_pinky_target(player),
-                        Name::Inky => calc_inky_target(blinky, player),
-                        Name::Clyde => calc_clyde_target(g.pos, plr),
-                    };
-                    g.move_to(map, target);
-                }
-                GhostMode::Scatter => {
-                    let target = match g.name {
-                        Name::Blinky => BLINKY_HOME,
-                        Name::Pinky => PINKY_HOME,
-                        Name::Inky => INKY_HOME,
-                        Name::Clyde => CLYDE_HOME,
-                    };
-                    g.move_to(map, target);
-                }
+        self.release();
+        for i in 0..self.ghosts.len() {
+            let speed = self.speed(&self.ghosts[i], map);
+            self.ghosts[i].progress += speed;
+            while self.ghosts[i].progress >= TILE {
+                self.ghosts[i].progress -= TILE;
+                self.step(i, map, player, rg);
             }
         }
         if self.ghost_mode == GhostMode::Frightened {
             self.frightened_timer = self.frightened_timer.saturating_sub(1);
             if self.frightened_timer == 0 {
-                self.ghost_mode = GhostMode::Chase;
+                self.ghost_mode = self.scheduled_mode();
+                self.eat_combo = 0;
+                for g in self.ghosts.iter_mut() {
+                    g.frightened = false;
+                }
             }
-        } else {
-            self.mode_timer = self.mode_timer.saturating_sub(1);
+        } else if self.mode_phase < self.schedule.len() {
+            // The schedule is paused while the ghosts are frightened.
+            self.mode_timer -= 1;
             if self.mode_timer == 0 {
-                self.mode_timer = GHOST_MODE_TIMER;
-                self.ghost_mode = if self.ghost_mode == GhostMode::Chase && self.num_scatters > 0 {
-                    self.num_scatters -= 1;
-                    GhostMode::Scatter
-                } else {
-                    GhostMode::Chase
-                }
+                self.mode_phase += 1;
+                self.mode_timer = self.schedule.get(self.mode_phase).copied().unwrap_or(0);
+                self.ghost_mode = self.scheduled_mode();
+                self.reverse();
             }
         }
     }
 
-    pub fn interact_with_player(&mut self, plr: (i32, i32)) -> Option<Interaction> {
-        if self.ghost_mode == GhostMode::Frightened {
-            let mut killed = 0;
-            for g in self.ghosts.iter_mut() {
-                if g.pos == plr || g.last_pos == plr {
-                    *g = Ghost::new(g.name, (1, 1), 10); // TODO pass right values from params
-                    killed += 1;
-                }
+    /// Count a dot eaten by Pac-Man towards letting the next ghost out of the house.
+    pub fn dot_eaten(&mut self) {
+        self.idle_ticks = 0;
+        if let Some(dots) = &mut self.global_dots {
+            *dots += 1;
+        } else if let Some(i) = self.waiting() {
+            self.dot_counters[i] += 1;
+        }
+    }
+
+    /// The ghost that leaves the house next, the first one in the order Blinky, Pinky,
+    /// Inky, Clyde that is still inside.
+    fn waiting(&self) -> Option<usize> {
+        self.ghosts.iter().position(|g| g.in_house)
+    }
+
+    /// Dots the ghost `i` has to count before it leaves the house.
+    fn dot_limit(&self, i: usize) -> u32 {
+        match i {
+            0 => 0,
+            1 => self.rules.pinky_dots,
+            2 => self.rules.inky_dots,
+            _ => self.rules.clyde_dots,
+        }
+    }
+
+    /// Let the waiting ghost out once it counted enough dots, or once Pac-Man did not
+    /// eat a dot for a while. At most one ghost leaves every tick.
+    fn release(&mut self) {
+        self.idle_ticks = self.idle_ticks.saturating_add(1);
+        let i = match self.waiting() {
+            Some(i) => i,
+            None => {
+                self.global_dots = None;
+                return;
             }
-            if killed == 0 {
-                None
-            } else {
-                Some(Interaction::KillGhost(killed))
+        };
+        let idle = self.rules.idle_release_ticks;
+        let leave = match self.global_dots {
+            Some(dots) if i == 3 && dots >= GLOBAL_DOT_LIMITS[3] => {
+                // Back to the ghosts' own counters.
+                self.global_dots = None;
+                self.dot_counters[i] >= self.dot_limit(i)
+            }
+            Some(dots) => dots >= GLOBAL_DOT_LIMITS[i],
+            None => self.dot_counters[i] >= self.dot_limit(i),
+        };
+        if leave || (idle > 0 && self.idle_ticks >= idle) {
+            self.ghosts[i].in_house = false;
+            self.idle_ticks = 0;
+            if i == 3 {
+                self.elroy_paused = false;
             }
+        }
+    }
+
+    /// How far Blinky is into Cruise Elroy, 0 when he is not. Once few enough pellets are
+    /// left he speeds up and keeps chasing Pac-Man during scatter, with 1, and speeds up
+    /// again with half of those pellets left, with 2.
+    pub fn elroy(&self, map: &Map) -> u8 {
+        let left = map.pellets();
+        if self.elroy_paused || self.elroy_pellets == 0 || left > self.elroy_pellets {
+            0
+        } else if left > self.elroy_pellets / 2 {
+            1
         } else {
-            if self
-                .ghosts
-                .iter()
-                .any(|g| g.pos == plr || g.last_pos == plr)
-            {
-                Some(Interaction::KillPlayer)
-            } else {
-                None
+            2
+        }
+    }
+
+    /// For every ghost in the house, the dots it counted and the dots it needs to leave.
+    /// After a lost life these are the dots of the shared counter.
+    pub fn house_counters(&self) -> [Option<(u32, u32)>; 4] {
+        let mut counters = [None; 4];
+        for (i, counter) in counters.iter_mut().enumerate() {
+            if self.ghosts[i].in_house {
+                *counter = Some(match self.global_dots {
+                    Some(dots) => (dots, GLOBAL_DOT_LIMITS[i]),
+                    None => (self.dot_counters[i], self.dot_limit(i)),
+                });
+            }
+        }
+        counters
+    }
+
+    /// Ticks since Pac-Man last ate a dot, and the ticks after which a ghost leaves anyway.
+    pub fn idle_ticks(&self) -> (u16, u16) {
+        (self.idle_ticks, self.rules.idle_release_ticks)
+    }
+
+    /// Speed of `g` on its current tile.
+    fn speed(&self, g: &Ghost, map: &Map) -> u32 {
+        if g.eaten {
+            self.speeds.eyes
+        } else if map.is_tunnel(g.pos.0, g.pos.1) {
+            self.speeds.ghost_tunnel
+        } else if g.frightened {
+            self.speeds.ghost_frightened
+        } else if matches!(g.name, Name::Blinky) {
+            match self.elroy(map) {
+                0 => self.speeds.ghost,
+                1 => self.speeds.elroy,
+                _ => self.speeds.elroy_2,
+            }
+        } else {
+            self.speeds.ghost
+        }
+    }
+
+    /// Move ghost `i` one tile.
+    fn step<RG: RandGen>(
+        &mut self,
+        i: usize,
+        map: &Map,
+        player: (i32, i32, Direction),
+        rg: &mut RG,
+    ) {
+        let respawn = self.respawn;
+        let g = &mut self.ghosts[i];
+        if g.eaten {
+            g.return_home(map, respawn);
+            return;
+        }
+        if g.in_house {
+            g.house_move(map, rg);
+            return;
+        }
+        if g.reverse {
+            g.reverse = false;
+            if g.last_pos != (i32::MIN, i32::MIN) {
+                g.change_pos(g.last_pos);
+                return;
             }
         }
+
+        // Ghosts that came back during a power up follow the schedule again.
+        let mut mode = if g.frightened {
+            GhostMode::Frightened
+        } else {
+            self.scheduled_mode()
+        };
+        if matches!(self.ghosts[i].name, Name::Blinky)
+            && mode == GhostMode::Scatter
+            && self.elroy(map) > 0
+        {
+            mode = GhostMode::Chase;
+        }
+        let view = GhostView {
+            map,
+            player,
+            ghosts: &self.ghosts,
+            me: i,
+            mode,
+        };
+        let next_move = self.brains[i].brain().next_move(&view, rg);
+        let g = &mut self.ghosts[i];
+        match next_move {
+            Move::Towards(target) => g.move_to(map, target),
+            Move::Flee => g.flee(map, rg),
+            Move::HouseMove => g.house_move(map, rg),
+        }
+    }
+
+    /// Frightened ghosts that touch Pac-Man are eaten, unless another ghost catches him.
+    pub fn interact_with_player(&mut self, plr: (i32, i32)) -> Option<Interaction> {
+        let touches = |g: &Ghost| !g.eaten && (g.pos == plr || g.last_pos == plr);
+        if self.ghosts.iter().any(|g| touches(g) && !g.frightened) {
+            return Some(Interaction::KillPlayer);
+        }
+        let mut killed = Vec::new();
+        for g in self.ghosts.iter_mut().filter(|g| touches(g)) {
+            let points = self
+                .rules
+                .score_ghost
+                .saturating_mul(1 << self.eat_combo.min(3));
+            self.eat_combo = self.eat_combo.saturating_add(1);
+            killed.push((points, g.pos));
+            g.eaten = true;
+            g.frightened = false;
+            g.reverse = false;
+        }
+        if killed.is_empty() {
+            None
+        } else {
+            Some(Interaction::KillGhosts(killed))
+        }
     }
 
+    /// Put the ghosts back in the house after Pac-Man lost a life. They keep their own
+    /// dot counters, but leave on the shared counter until Clyde is out.
     pub fn reset(&mut self) {
-        *self = Ghosts::new();
+        let dot_counters = self.dot_counters;
+        *self = Ghosts::from_starts(
+            self.starts,
+            self.respawn,
+            self.rules.clone(),
+            self.brains.clone(),
+            self.speeds,
+            self.schedule,
+            self.elroy_pellets,
+        );
+        self.dot_counters = dot_counters;
+        self.global_dots = Some(0);
+        self.elroy_paused = true;
+    }
+
+    pub(super) fn save(&self, w: &mut StateWriter) {
+        w.field("ghost_mode", self.ghost_mode.as_str());
+        w.field("mode_phase", self.mode_phase);
+        w.field("mode_timer", self.mode_timer);
+        w.field("frightened_timer", self.frightened_timer);
+        w.field("eat_combo", self.eat_combo);
+        w.field("idle_ticks", self.idle_ticks);
+        w.field("elroy_paused", self.elroy_paused);
+        match self.global_dots {
+            Some(dots) => w.field("global_dots", dots),
+            None => w.field("global_dots", "none"),
+        }
+        for (i, g) in self.ghosts.iter().enumerate() {
+            w.pos(&format!("ghost.{}.pos", i), g.pos);
+            w.pos(&format!("ghost.{}.last_pos", i), g.last_pos);
+            w.field(&format!("ghost.{}.in_house", i), g.in_house);
+            w.field(&format!("ghost.{}.dots", i), self.dot_counters[i]);
+            w.field(&format!("ghost.{}.reverse", i), g.reverse);
+            w.field(&format!("ghost.{}.eaten", i), g.eaten);
+            w.field(&format!("ghost.{}.frightened", i), g.frightened);
+            w.field(&format!("ghost.{}.progress", i), g.progress);
+        }
+    }
+
+    /// Ghosts as written by `save` on `map`, the names and their order come from `self`.
+    pub(super) fn load(&self, map: &Map, r: &StateReader) -> Result<Ghosts, String> {
+        let mut ghosts = Ghosts {
+            ghosts: self.ghosts.clone(),
+            starts: self.starts,
+            respawn: self.respawn,
+            rules: self.rules.clone(),
+            brains: self.brains.clone(),
+            schedule: self.schedule,
+            speeds: self.speeds,
+            elroy_pellets: self.elroy_pellets,
+            elroy_paused: r.field("elroy_paused")?,
+            ghost_mode: GhostMode::from_str(r.str("ghost_mode")?)
+                .ok_or_else(|| r.bad_value("ghost_mode"))?,
+            mode_phase: r.field("mode_phase")?,
+            mode_timer: r.field("mode_timer")?,
+            frightened_timer: r.field("frightened_timer")?,
+            eat_combo: r.field("eat_combo")?,
+            dot_counters: [0; 4],
+            global_dots: match r.str("global_dots")? {
+                "none" => None,
+                _ => Some(r.field("global_dots")?),
+            },
+            idle_ticks: r.field("idle_ticks")?,
+        };
+        // A phase of the schedule that is still running has ticks left.
+        if ghosts.mode_phase > ghosts.schedule.len() {
+            return Err(r.bad_value("mode_phase"));
+        }
+        if ghosts.mode_timer == 0 && ghosts.mode_phase < ghosts.schedule.len() {
+            return Err(r.bad_value("mode_timer"));
+        }
+        let pos = |key: &str| match r.pos(key)? {
+            (x, y) if map.get(x, y).is_none() => Err(r.bad_value(key)),
+            pos => Ok(pos),
+        };
+        for (i, g) in ghosts.ghosts.iter_mut().enumerate() {
+            g.pos = pos(&format!("ghost.{}.pos", i))?;
+            let last_pos = format!("ghost.{}.last_pos", i);
+            // Ghosts that did not move yet have no last position.
+            g.last_pos = match r.pos(&last_pos)? {
+                (i32::MIN, i32::MIN) => (i32::MIN, i32::MIN),
+                _ => pos(&last_pos)?,
+            };
+            g.in_house = r.field(&format!("ghost.{}.in_house", i))?;
+            ghosts.dot_counters[i] = r.field(&format!("ghost.{}.dots", i))?;
+            g.reverse = r.field(&format!("ghost.{}.reverse", i))?;
+            g.eaten = r.field(&format!("ghost.{}.eaten", i))?;
+            g.frightened = r.field(&format!("ghost.{}.frightened", i))?;
+            g.progress = r.field(&format!("ghost.{}.progress", i))?;
+        }
+        Ok(ghosts)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Hash)]
 pub struct Ghost {
     name: Name,
     pos: (i32, i32),
     last_pos: (i32, i32),
-    house_timer: u16,
+    /// Waiting in the house, see [`Ghosts::house_counters`].
+    in_house: bool,
+    /// Turn around on the next move, set when the ghosts change mode.
+    reverse: bool,
+    /// Only the eyes are left, on their way back to the house.
+    eaten: bool,
+    /// Out when Pac-Man ate the last power up, cleared when it wears off or the ghost
+    /// is eaten.
+    frightened: bool,
+    /// Progress towards the next tile, see [`TILE`].
+    progress: u32,
 }
 
 impl Ghost {
-    fn new(name: Name, pos: (i32, i32), house_timer: u16) -> Self {
+    fn new(name: Name, pos: (i32, i32), in_house: bool) -> Self {
         // let start_p = match name {
         //     Name::Blinky => (x_mid, y_mid),
         //     Name::Pinky => (x_mid, y_mid - 1),
@@ -176,18 +548,20 @@ impl Ghost {
         // };
         Ghost {
             pos,
-            last_pos: (i32::min_value(), i32::min_value()),
-            // house_timer: match name {
-            //     Name::Blinky => 2,
-            //     Name::Pinky => 10,
-            //     Name::Inky => 20,
-            //     Name::Clyde => 30,
-            // },
-            house_timer,
+            last_pos: (i32::MIN, i32::MIN),
+            in_house,
+            reverse: false,
+            eaten: false,
+            frightened: false,
+            progress: 0,
             name,
         }
     }
 
+    pub fn name(&self) -> Name {
+        self.name
+    }
+
     pub fn x(&self) -> i32 {
         self.pos.0
     }
@@ -196,10 +570,64 @@ impl Ghost {
         self.pos.1
     }
 
+    /// Progress towards the next tile, see [`TILE`].
+    pub fn progress(&self) -> u32 {
+        self.progress
+    }
+
+    /// Direction of the last move, `(0, 0)` before the first one.
+    pub fn heading(&self) -> (i32, i32) {
+        if self.last_pos == (i32::MIN, i32::MIN) {
+            return (0, 0);
+        }
+        // Moves through a tunnel wrap around the map.
+        let d = |from: i32, to: i32| match to - from {
+            0 => 0,
+            1 => 1,
+            -1 => -1,
+            n => -n.signum(),
+        };
+        (
+            d(self.last_pos.0, self.pos.0),
+            d(self.last_pos.1, self.pos.1),
+        )
+    }
+
+    /// True while only the eyes are left, see [`Ghosts::interact_with_player`].
+    pub fn eaten(&self) -> bool {
+        self.eaten
+    }
+
+    /// True while the ghost can be eaten, see [`Ghosts::frighten`].
+    pub fn frightened(&self) -> bool {
+        self.frightened
+    }
+
     fn move_to(&mut self, map: &Map, mut target: (i32, i32)) {
         if map.is_house(self.pos.0, self.pos.1) {
-            target = (13, 11); // (14, 11)
+            target = map.house_door();
         }
+        self.move_towards(map, target);
+    }
+
+    /// Move the eyes one tile closer to the door, and from there into the house to
+    /// `respawn`, where the ghost comes back.
+    fn return_home(&mut self, map: &Map, respawn: (i32, i32)) {
+        let door = map.house_door();
+        let target = if self.pos == door || map.is_house(self.pos.0, self.pos.1) {
+            respawn
+        } else {
+            door
+        };
+        self.move_towards(map, target);
+        if self.pos == respawn {
+            // Eaten ghosts leave again right away.
+            *self = Ghost::new(self.name, respawn, false);
+        }
+    }
+
+    /// Take the open tile closest to `target`, without turning around.
+    fn move_towards(&mut self, map: &Map, target: (i32, i32)) {
         let options = self.get_options(map.width);
         let decision = options
             .iter()
@@ -235,7 +663,6 @@ impl Ghost {
                 break;
             }
         }
-        self.house_timer = self.house_timer.saturating_sub(1);
     }
 
     fn change_pos(&mut self, to: (i32, i32)) {
@@ -253,7 +680,7 @@ impl Ghost {
                 x
             }
         };
-        vec![
+        [
             (self.pos.0 + 1, self.pos.1),
             (self.pos.0 - 1, self.pos.1),
             (self.pos.0, self.pos.1 + 1),
@@ -266,30 +693,6 @@ impl Ghost {
     }
 }
 
-fn calc_pinky_target(player: (i32, i32, Direction)) -> (i32, i32) {
-    let v = player.2.to_vector();
-    let plr = (player.0, player.1);
-    (plr.0 + v.0 * 4, plr.1 + v.1 * 4)
-}
-
-fn calc_inky_target(blinky: (i32, i32), player: (i32, i32, Direction)) -> (i32, i32) {
-    let plr = (player.0, player.1);
-    let mid_tgt = {
-        let v = player.2.to_vector();
-        (plr.0 + v.0 * 2, plr.1 + v.1 * 2)
-    };
-    let tgt_vec = ((mid_tgt.0 - blinky.0) * 2, (mid_tgt.1 - blinky.1) * 2);
-    (blinky.0 + tgt_vec.0, blinky.1 + tgt_vec.1)
-}
-
-fn calc_clyde_target(clyde: (i32, i32), plr: (i32, i32)) -> (i32, i32) {
-    if (((clyde.0 - plr.0).pow(2) + (clyde.1 - plr.1).pow(2)) as f64).sqrt() < 8.0 {
-        CLYDE_HOME
-    } else {
-        plr
-    }
-}
-
 // // DEBUG VIEWS
 // #[allow(dead_code)]
 // impl Ghosts {
@@ -306,3 +709,232 @@ fn calc_clyde_target(clyde: (i32, i32), plr: (i32, i32)) -> (i32, i32) {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::Tile;
+    use crate::game::testing::new_game;
+
+    #[test]
+    fn test_mode_schedule_and_reversal() {
+        assert_eq!(mode_schedule(1, 6), [42, 120, 42, 120, 30, 120, 30]);
+        assert_eq!(mode_schedule(5, 6)[5..], [6222, 1]);
+
+        let mut game = new_game(3);
+        let map = game.map.clone();
+        let player = (1, 1, Direction::Left);
+        let ghosts = &mut game.ghosts;
+        assert_eq!(ghosts.ghost_mode(), GhostMode::Scatter);
+        for _ in 0..42 {
+            ghosts.move_ghosts(&map, player, &mut game.rg);
+        }
+        assert_eq!(ghosts.ghost_mode(), GhostMode::Chase);
+
+        // Every ghost outside the house goes back to where it came from.
+        let before = ghosts.ghosts.clone();
+        ghosts.move_ghosts(&map, player, &mut game.rg);
+        assert!(before.iter().any(|g| !g.in_house));
+        for (b, g) in before.iter().zip(ghosts.ghosts.iter()) {
+            if !b.in_house {
+                assert_eq!(g.pos, b.last_pos);
+            }
+        }
+
+        // The schedule waits while the ghosts are frightened.
+        let timer = ghosts.mode_timer;
+        ghosts.frighten();
+        for _ in 0..ghosts.rules.frightened_ticks {
+            ghosts.move_ghosts(&map, player, &mut game.rg);
+        }
+        assert_eq!(ghosts.ghost_mode(), GhostMode::Chase);
+        assert_eq!(ghosts.mode_timer, timer);
+    }
+
+    fn points(interaction: Option<Interaction>) -> Vec<u32> {
+        match interaction {
+            Some(Interaction::KillGhosts(killed)) => killed.iter().map(|(p, _)| *p).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Ghosts of a new game that are all out of the house.
+    fn out_of_house() -> Ghosts {
+        let mut ghosts = new_game(1).ghosts;
+        for g in ghosts.ghosts.iter_mut() {
+            g.in_house = false;
+        }
+        ghosts
+    }
+
+    #[test]
+    fn test_eat_combo() {
+        let mut ghosts = out_of_house();
+        let plr = (5, 5);
+        ghosts.frighten();
+        for g in ghosts.ghosts.iter_mut() {
+            g.pos = plr;
+        }
+        assert_eq!(
+            points(ghosts.interact_with_player(plr)),
+            [200, 400, 800, 1600]
+        );
+
+        // Eyes cannot be eaten again, and another power up starts counting again.
+        let mut ghosts = out_of_house();
+        ghosts.frighten();
+        ghosts.ghosts[0].pos = plr;
+        assert_eq!(points(ghosts.interact_with_player(plr)), [200]);
+        ghosts.frighten();
+        for g in ghosts.ghosts.iter_mut() {
+            g.pos = plr;
+        }
+        assert_eq!(points(ghosts.interact_with_player(plr)), [200, 400, 800]);
+
+        // Points stop at the highest score instead of wrapping around.
+        let mut ghosts = out_of_house();
+        ghosts.rules.score_ghost = 1 << 31;
+        ghosts.frighten();
+        ghosts.ghosts[0].pos = plr;
+        ghosts.ghosts[1].pos = plr;
+        assert_eq!(
+            points(ghosts.interact_with_player(plr)),
+            [1 << 31, u32::MAX]
+        );
+    }
+
+    fn in_house(ghosts: &Ghosts) -> Vec<bool> {
+        ghosts.ghosts.iter().map(|g| g.in_house).collect()
+    }
+
+    #[test]
+    fn test_house_release() {
+        // Blinky and Pinky need no dots, but only one ghost leaves per tick.
+        let mut ghosts = new_game(1).ghosts;
+        ghosts.release();
+        assert_eq!(in_house(&ghosts), [false, true, true, true]);
+        ghosts.release();
+        assert_eq!(
+            ghosts.house_counters()[1..],
+            [None, Some((0, 30)), Some((0, 60))]
+        );
+
+        // Only the ghost that leaves next counts the dots.
+        for _ in 0..30 {
+            ghosts.dot_eaten();
+        }
+        ghosts.release();
+        assert_eq!(in_house(&ghosts), [false, false, false, true]);
+        assert_eq!(ghosts.house_counters()[3], Some((0, 60)));
+
+        // Not eating makes the next ghost leave anyway.
+        for _ in 0..23 {
+            ghosts.release();
+        }
+        assert_eq!(ghosts.idle_ticks(), (23, 24));
+        assert!(ghosts.ghosts[3].in_house);
+        ghosts.release();
+        assert!(!ghosts.ghosts[3].in_house);
+
+        // After a lost life the ghosts leave on the shared counter.
+        ghosts.reset();
+        ghosts.release();
+        ghosts.release();
+        assert_eq!(ghosts.house_counters()[1], Some((0, 7)));
+        for _ in 0..7 {
+            ghosts.dot_eaten();
+        }
+        ghosts.release();
+        assert_eq!(in_house(&ghosts), [false, false, true, true]);
+        assert_eq!(ghosts.house_counters()[2], Some((7, 17)));
+    }
+
+    #[test]
+    fn test_cruise_elroy() {
+        let mut game = new_game(1);
+        let mut map = game.map.clone();
+        let ghosts = &mut game.ghosts;
+        let eat_until = |map: &mut Map, left: u32| {
+            for y in 0..map.height as i32 {
+                for x in 0..map.width as i32 {
+                    if map.pellets() > left && matches!(map.get(x, y), Some(Tile::Dot)) {
+                        map.consume(x, y);
+                    }
+                }
+            }
+        };
+        assert_eq!(ghosts.elroy(&map), 0);
+        eat_until(&mut map, 20);
+        assert_eq!(ghosts.elroy(&map), 1);
+        assert_eq!(ghosts.speed(&ghosts.ghosts[0], &map), ghosts.speeds.elroy);
+        assert_eq!(ghosts.speed(&ghosts.ghosts[1], &map), ghosts.speeds.ghost);
+        eat_until(&mut map, 10);
+        assert_eq!(ghosts.elroy(&map), 2);
+
+        // After a lost life Blinky waits for Clyde to leave the house.
+        ghosts.reset();
+        assert_eq!(ghosts.elroy(&map), 0);
+        while ghosts.ghosts[3].in_house {
+            ghosts.release();
+        }
+        assert_eq!(ghosts.elroy(&map), 2);
+    }
+
+    #[test]
+    fn test_eyes_return_to_house() {
+        let mut game = new_game(1);
+        let map = game.map.clone();
+        let ghosts = &mut game.ghosts;
+        let plr = (20, 6);
+        ghosts.ghosts[0].in_house = false;
+        ghosts.frighten();
+        ghosts.ghosts[0].pos = plr;
+        assert!(ghosts.interact_with_player(plr).is_some());
+        assert!(ghosts.ghosts[0].eaten());
+
+        // Eyes move two tiles a tick and do not catch Pac-Man.
+        ghosts.ghost_mode = GhostMode::Chase;
+        let start = ghosts.ghosts[0].pos;
+        ghosts.move_ghosts(&map, (1, 1, Direction::Left), &mut game.rg);
+        let (x, y) = ghosts.ghosts[0].pos;
+        assert_eq!((x - start.0).abs() + (y - start.1).abs(), 2);
+        assert!(ghosts.interact_with_player((x, y)).is_none());
+
+        let mut ticks = 1;
+        while ghosts.ghosts[0].eaten() {
+            ghosts.move_ghosts(&map, (1, 1, Direction::Left), &mut game.rg);
+            ticks += 1;
+        }
+        assert!(ticks < 10);
+        assert_eq!(ghosts.ghosts[0].pos, map.house_respawn());
+        assert!(!ghosts.ghosts[0].in_house);
+    }
+
+    #[test]
+    fn test_respawned_ghost_is_not_frightened() {
+        let mut game = new_game(1);
+        let map = game.map.clone();
+        let ghosts = &mut game.ghosts;
+        let player = (1, 1, Direction::Left);
+        ghosts.release();
+        ghosts.frighten();
+        let plr = ghosts.ghosts[0].pos;
+        assert!(ghosts.ghosts[0].frightened());
+        assert!(!ghosts.ghosts[1].frightened());
+        assert!(ghosts.interact_with_player(plr).is_some());
+        while ghosts.ghosts[0].eaten() {
+            ghosts.move_ghosts(&map, player, &mut game.rg);
+        }
+
+        // Back during the same power up, Blinky is no longer edible and catches Pac-Man.
+        assert_eq!(ghosts.ghost_mode(), GhostMode::Frightened);
+        let blinky = &ghosts.ghosts[0];
+        assert!(!blinky.frightened());
+        assert_eq!(ghosts.speed(blinky, &map), ghosts.speeds.ghost);
+        let plr = blinky.pos;
+        assert!(matches!(
+            ghosts.interact_with_player(plr),
+            Some(Interaction::KillPlayer)
+        ));
+    }
+}
@@ -1,3 +1,8 @@
+use std::fmt;
+
+use super::save_state::{StateReader, StateWriter};
+use crate::sim::stable_hash;
+
 // const MAP_STR: [&'static str; 10] = [
 //     "############################",
 //     "#................X......#..#",
@@ -11,7 +16,7 @@
 //     "############################",
 // ];
 
-fn pellet_coords(map_str: &Vec<Vec<char>>) -> Vec<(usize, usize)> {
+fn pellet_coords(map_str: &[Vec<char>]) -> Vec<(usize, usize)> {
     map_str
         .iter()
         .enumerate()
@@ -46,15 +51,32 @@ fn pellet_coords(map_str: &Vec<Vec<char>>) -> Vec<(usize, usize)> {
 //     }
 // }
 
+/// Marks where Pac-Man starts.
+pub const PLAYER_MARKER: char = 'S';
+/// Markers for where Blinky, Pinky, Inky and Clyde start, in that order.
+pub const GHOST_MARKERS: [char; 4] = ['B', 'P', 'I', 'C'];
+/// Starts the option lines after the tiles.
+const OPTION_MARKER: char = '@';
+
+#[derive(Clone, Hash)]
 pub struct Map {
     pub width: usize,
     pub height: usize,
     tiles: Vec<Tile>,
     pellets: u32,
     pellet_coords: Vec<(usize, usize)>,
+    hash: u64,
+    player_start: (i32, i32),
+    ghost_starts: [(i32, i32); 4],
+    scatter_targets: [(i32, i32); 4],
+    house_door: (i32, i32),
+    house_respawn: (i32, i32),
+    fruit_tile: (i32, i32),
+    /// Tiles where ghosts slow down, one for every tile of the map.
+    tunnels: Vec<bool>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum Tile {
     Wall,
     House,
@@ -74,34 +96,345 @@ fn tile_from_char(c: char) -> Option<Tile> {
     }
 }
 
+/// Tiles the player cannot walk on.
+fn is_blocked(tile: Tile) -> bool {
+    matches!(tile, Tile::Wall | Tile::House)
+}
+
+fn tile_to_char(tile: Tile) -> char {
+    match tile {
+        Tile::Wall => '#',
+        Tile::Dot => '.',
+        Tile::Empty => ' ',
+        Tile::PowerUp => 'X',
+        Tile::House => 'H',
+    }
+}
+
+/// Why a map could not be parsed. Rows and columns count from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapError {
+    Empty,
+    RowLength {
+        row: usize,
+        len: usize,
+        expected: usize,
+    },
+    UnknownChar {
+        row: usize,
+        col: usize,
+        c: char,
+    },
+    MissingStart(char),
+    DuplicateStart {
+        row: usize,
+        col: usize,
+        c: char,
+    },
+    /// A row open on one side only, walking through it would end up inside a wall.
+    OneSidedTunnel {
+        row: usize,
+    },
+    /// An open tile in the top or bottom row, the map can only wrap around left to right.
+    OpenEdge {
+        row: usize,
+        col: usize,
+    },
+    NoGhostHouse,
+    GhostHouseClosed,
+    UnreachablePellet {
+        row: usize,
+        col: usize,
+    },
+    /// A line after the tiles that is not a valid `@key=x,y` option.
+    InvalidOption {
+        row: usize,
+        line: String,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "Map is empty."),
+            MapError::RowLength { row, len, expected } => {
+                write!(f, "Row {} has {} columns, expected {}.", row, len, expected)
+            }
+            MapError::UnknownChar { row, col, c } => write!(
+                f,
+                "Unknown character '{}' at row {}, column {}.",
+                c, row, col
+            ),
+            MapError::MissingStart(c) => write!(f, "Map has no start '{}'.", c),
+            MapError::DuplicateStart { row, col, c } => write!(
+                f,
+                "Second start '{}' at row {}, column {}, there can only be one.",
+                c, row, col
+            ),
+            MapError::OneSidedTunnel { row } => {
+                write!(f, "Row {} is open on one side only.", row)
+            }
+            MapError::OpenEdge { row, col } => write!(
+                f,
+                "Open tile at row {}, column {} on the top or bottom edge.",
+                row, col
+            ),
+            MapError::NoGhostHouse => write!(f, "Map has no ghost house 'H'."),
+            MapError::GhostHouseClosed => write!(f, "The ghost house has no exit."),
+            MapError::UnreachablePellet { row, col } => write!(
+                f,
+                "Pellet at row {}, column {} cannot be reached from the start.",
+                row, col
+            ),
+            MapError::InvalidOption { row, line } => {
+                write!(f, "Invalid map option '{}' at row {}.", line, row)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
 impl Map {
-    pub fn new(map_str: Vec<Vec<char>>) -> Self {
-        let map_width = map_str[0].len();
-        let map_height = map_str.len();
-        let pellet_coords = pellet_coords(&map_str);
-        let tiles: Vec<Tile> = map_str
-            .into_iter()
-            .flatten()
-            .filter_map(tile_from_char)
-            .collect();
-        assert_eq!(tiles.len(), map_width * map_height);
-        let n_pellets = tiles
+    /// Read a map from its rows and check that it can be played: all starts are there
+    /// once, every pellet can be reached, the ghosts can leave their house and the player
+    /// cannot walk off the map.
+    ///
+    /// The tiles can be followed by `@key=x,y` lines to override the positions that are
+    /// otherwise derived from the layout: `scatter.blinky`, `scatter.pinky`,
+    /// `scatter.inky`, `scatter.clyde`, `door`, `respawn` and `fruit`, where the bonus fruit
+    /// appears instead of where the player starts. The last three must be on the map and not
+    /// on a wall.
+    pub fn parse(rows: Vec<Vec<char>>) -> Result<Self, MapError> {
+        let hash = stable_hash(&rows);
+        let n_tile_rows = rows
             .iter()
-            .filter(|c| if let Tile::Dot = c { true } else { false })
-            .count() as u32;
-        Map {
+            .position(|row| row.first() == Some(&OPTION_MARKER))
+            .unwrap_or(rows.len());
+        let map_str = &rows[..n_tile_rows];
+        let map_width = map_str.first().map(|row| row.len()).unwrap_or(0);
+        let map_height = map_str.len();
+        if map_width == 0 {
+            return Err(MapError::Empty);
+        }
+        let pellet_coords = pellet_coords(map_str);
+        let mut tiles = Vec::with_capacity(map_width * map_height);
+        let mut player_start = None;
+        let mut ghost_starts = [None; 4];
+        for (y, row) in map_str.iter().enumerate() {
+            if row.len() != map_width {
+                return Err(MapError::RowLength {
+                    row: y,
+                    len: row.len(),
+                    expected: map_width,
+                });
+            }
+            for (x, c) in row.iter().enumerate() {
+                // Starts are empty tiles.
+                let start = if *c == PLAYER_MARKER {
+                    Some(&mut player_start)
+                } else {
+                    GHOST_MARKERS
+                        .iter()
+                        .position(|m| m == c)
+                        .map(|i| &mut ghost_starts[i])
+                };
+                if let Some(start) = start {
+                    if start.is_some() {
+                        return Err(MapError::DuplicateStart {
+                            row: y,
+                            col: x,
+                            c: *c,
+                        });
+                    }
+                    *start = Some((x as i32, y as i32));
+                    tiles.push(Tile::Empty);
+                    continue;
+                }
+                let tile = tile_from_char(*c).ok_or(MapError::UnknownChar {
+                    row: y,
+                    col: x,
+                    c: *c,
+                })?;
+                tiles.push(tile);
+            }
+        }
+        let player_start = player_start.ok_or(MapError::MissingStart(PLAYER_MARKER))?;
+        let mut starts = [(0, 0); 4];
+        for (i, start) in ghost_starts.iter().enumerate() {
+            starts[i] = start.ok_or(MapError::MissingStart(GHOST_MARKERS[i]))?;
+        }
+        let n_pellets = tiles.iter().filter(|c| matches!(c, Tile::Dot)).count() as u32;
+        let (w, h) = (map_width as i32, map_height as i32);
+        let mut map = Map {
             width: map_width,
             height: map_height,
             tiles,
             pellet_coords,
             pellets: n_pellets,
+            hash,
+            player_start,
+            ghost_starts: starts,
+            // Just outside the corners, so the ghosts circle around them.
+            scatter_targets: [(w - 3, -2), (2, -2), (w - 1, h), (0, h)],
+            house_door: (0, 0),
+            house_respawn: (0, 0),
+            fruit_tile: player_start,
+            tunnels: Vec::new(),
+        };
+        map.check_edges()?;
+        map.check_ghost_house()?;
+        map.check_reachable()?;
+        map.house_door = map.find_house_door();
+        map.house_respawn = map.find_house_respawn();
+        map.tunnels = map.find_tunnels();
+        for (row, line) in rows.iter().enumerate().skip(n_tile_rows) {
+            map.apply_option(line)
+                .ok_or_else(|| MapError::InvalidOption {
+                    row,
+                    line: line.iter().collect(),
+                })?;
+        }
+        Ok(map)
+    }
+
+    /// First open tile next to the ghost house.
+    fn find_house_door(&self) -> (i32, i32) {
+        self.positions()
+            .filter(|(x, y)| self.is_house(*x, *y))
+            .flat_map(|(x, y)| vec![(x, y - 1), (x, y + 1), (x - 1, y), (x + 1, y)])
+            .find(|(x, y)| matches!(self.get(*x, *y), Some(t) if !is_blocked(t)))
+            .expect("checked by check_ghost_house")
+    }
+
+    /// House tile closest to the middle of the house.
+    fn find_house_respawn(&self) -> (i32, i32) {
+        let house: Vec<_> = self
+            .positions()
+            .filter(|(x, y)| self.is_house(*x, *y))
+            .collect();
+        let n = house.len() as i32;
+        let sum = house
+            .iter()
+            .fold((0, 0), |acc, (x, y)| (acc.0 + x, acc.1 + y));
+        // Distances to the middle `sum / n`, scaled by `n` to stay in integers.
+        *house
+            .iter()
+            .min_by_key(|(x, y)| (n * x - sum.0).pow(2) + (n * y - sum.1).pow(2))
+            .expect("checked by check_ghost_house")
+    }
+
+    /// Open tiles of the rows that wrap around, from the edges up to where the walls above
+    /// and below them end.
+    fn find_tunnels(&self) -> Vec<bool> {
+        let mut tunnels = vec![false; self.tiles.len()];
+        let enclosed = |x: i32, y: i32| {
+            !self.is_wall(x, y) && self.is_wall(x, y - 1) && self.is_wall(x, y + 1)
+        };
+        let w = self.width as i32;
+        for y in 0..self.height as i32 {
+            let from_left = (0..w).take_while(|x| enclosed(*x, y));
+            let from_right = (0..w).rev().take_while(|x| enclosed(*x, y));
+            for x in from_left.chain(from_right) {
+                tunnels[y as usize * self.width + x as usize] = true;
+            }
+        }
+        tunnels
+    }
+
+    /// Apply an `@key=x,y` line, returns `None` if it is not valid.
+    fn apply_option(&mut self, line: &[char]) -> Option<()> {
+        let line: String = line.iter().collect();
+        let (key, value) = line.strip_prefix(OPTION_MARKER)?.split_once('=')?;
+        let (x, y) = value.split_once(',')?;
+        let pos = (x.trim().parse().ok()?, y.trim().parse().ok()?);
+        let open = matches!(self.get(pos.0, pos.1), Some(t) if !matches!(t, Tile::Wall));
+        let target = match key {
+            // Like in the arcade game, scatter targets can lie outside of the map.
+            "scatter.blinky" => &mut self.scatter_targets[0],
+            "scatter.pinky" => &mut self.scatter_targets[1],
+            "scatter.inky" => &mut self.scatter_targets[2],
+            "scatter.clyde" => &mut self.scatter_targets[3],
+            "door" if open => &mut self.house_door,
+            "respawn" if open => &mut self.house_respawn,
+            "fruit" if open => &mut self.fruit_tile,
+            _ => return None,
+        };
+        *target = pos;
+        Some(())
+    }
+
+    fn check_edges(&self) -> Result<(), MapError> {
+        let (w, h) = (self.width as i32, self.height as i32);
+        for y in 0..h {
+            if self.is_wall(0, y) != self.is_wall(w - 1, y) {
+                return Err(MapError::OneSidedTunnel { row: y as usize });
+            }
+        }
+        for y in [0, h - 1] {
+            if let Some(x) = (0..w).find(|x| !self.is_wall(*x, y)) {
+                return Err(MapError::OpenEdge {
+                    row: y as usize,
+                    col: x as usize,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_ghost_house(&self) -> Result<(), MapError> {
+        let mut house = self
+            .positions()
+            .filter(|(x, y)| self.is_house(*x, *y))
+            .peekable();
+        if house.peek().is_none() {
+            return Err(MapError::NoGhostHouse);
+        }
+        for (x, y) in house {
+            for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if matches!(self.get(nx, ny), Some(t) if !is_blocked(t)) {
+                    return Ok(());
+                }
+            }
+        }
+        Err(MapError::GhostHouseClosed)
+    }
+
+    /// Flood fill from the player start, going through the tunnels at the sides.
+    fn check_reachable(&self) -> Result<(), MapError> {
+        let w = self.width as i32;
+        let index = |(x, y): (i32, i32)| y as usize * self.width + x as usize;
+        let mut reached = vec![false; self.tiles.len()];
+        let mut todo = vec![self.player_start];
+        reached[index(self.player_start)] = true;
+        while let Some((x, y)) = todo.pop() {
+            for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                let next = ((nx + w) % w, ny);
+                let open = matches!(self.get(next.0, next.1), Some(t) if !is_blocked(t));
+                if open && !reached[index(next)] {
+                    reached[index(next)] = true;
+                    todo.push(next);
+                }
+            }
+        }
+        match self.positions().find(|p| {
+            matches!(self.get(p.0, p.1), Some(Tile::Dot | Tile::PowerUp)) && !reached[index(*p)]
+        }) {
+            Some((x, y)) => Err(MapError::UnreachablePellet {
+                row: y as usize,
+                col: x as usize,
+            }),
+            None => Ok(()),
         }
     }
 
+    fn positions(&self) -> impl Iterator<Item = (i32, i32)> {
+        let w = self.width as i32;
+        (0..self.tiles.len() as i32).map(move |i| (i % w, i / w))
+    }
+
     pub fn get(&self, x: i32, y: i32) -> Option<Tile> {
-        if x < 0 || x >= self.width as i32 {
-            None
-        } else if y < 0 || y >= self.height as i32 {
+        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
             None
         } else {
             Some(self.tiles[self.width * y as usize + x as usize])
@@ -110,18 +443,16 @@ impl Map {
 
     // TODO: rename this
     pub fn is_wall(&self, x: i32, y: i32) -> bool {
-        match self.get(x, y) {
-            Some(Tile::Wall) => true,
-            Some(Tile::House) => true,
-            _ => false,
-        }
+        matches!(self.get(x, y), Some(Tile::Wall) | Some(Tile::House))
     }
 
     pub fn is_house(&self, x: i32, y: i32) -> bool {
-        match self.get(x, y) {
-            Some(Tile::House) => true,
-            _ => false,
-        }
+        matches!(self.get(x, y), Some(Tile::House))
+    }
+
+    /// True for the tiles of a tunnel, where ghosts move slower.
+    pub fn is_tunnel(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some() && self.tunnels[y as usize * self.width + x as usize]
     }
 
     fn set(&mut self, x: u32, y: u32, tile: Tile) {
@@ -137,21 +468,78 @@ impl Map {
     }
 
     pub fn scan_lines(&self) -> ScanLine<'_> {
-        ScanLine {
-            map: &self,
-            line: 0,
-        }
+        ScanLine { map: self, line: 0 }
     }
 
     pub fn pellets(&self) -> u32 {
         self.pellets
     }
 
-    pub fn reset(&mut self) {
-        for (x, y) in self.pellet_coords.iter().cloned() {
-            self.tiles[self.width * y + x] = Tile::Dot;
+    /// Hash of the layout the map was created from, used to match recordings to maps.
+    pub fn layout_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn player_start(&self) -> (i32, i32) {
+        self.player_start
+    }
+
+    /// Start positions of Blinky, Pinky, Inky and Clyde.
+    pub fn ghost_starts(&self) -> [(i32, i32); 4] {
+        self.ghost_starts
+    }
+
+    /// Targets of Blinky, Pinky, Inky and Clyde in scatter mode.
+    pub fn scatter_targets(&self) -> [(i32, i32); 4] {
+        self.scatter_targets
+    }
+
+    /// Where ghosts go to leave the house.
+    pub fn house_door(&self) -> (i32, i32) {
+        self.house_door
+    }
+
+    /// Where eaten ghosts come back.
+    pub fn house_respawn(&self) -> (i32, i32) {
+        self.house_respawn
+    }
+
+    /// Where the bonus fruit appears, the start of Pac-Man unless the map sets it.
+    pub fn fruit_tile(&self) -> (i32, i32) {
+        self.fruit_tile
+    }
+}
+
+impl Map {
+    pub(super) fn save(&self, w: &mut StateWriter) {
+        for (y, line) in self.scan_lines().enumerate() {
+            let row: String = line.iter().cloned().map(tile_to_char).collect();
+            w.field(&format!("map.{}", y), row);
+        }
+    }
+
+    /// Restore the tiles written by `save`. Only pellets can differ from the current map,
+    /// walls and the ghost house never change while playing.
+    pub(super) fn load(&mut self, r: &StateReader) -> Result<(), String> {
+        let mut tiles = Vec::with_capacity(self.tiles.len());
+        for y in 0..self.height {
+            let key = format!("map.{}", y);
+            let row = r.str(&key)?;
+            if row.chars().count() != self.width {
+                return Err(r.bad_value(&key));
+            }
+            for (x, c) in row.chars().enumerate() {
+                let tile = tile_from_char(c).ok_or_else(|| r.bad_value(&key))?;
+                let is_fixed = |t: Tile| matches!(t, Tile::Wall | Tile::House);
+                if is_fixed(tile) != is_fixed(self.tiles[self.width * y + x]) {
+                    return Err(r.bad_value(&key));
+                }
+                tiles.push(tile);
+            }
         }
-        self.pellets = self.pellet_coords.len() as u32;
+        self.pellets = tiles.iter().filter(|t| matches!(t, Tile::Dot)).count() as u32;
+        self.tiles = tiles;
+        Ok(())
     }
 }
 
@@ -178,6 +566,122 @@ impl<'a> Iterator for ScanLine<'a> {
 #[allow(dead_code)]
 impl Map {
     pub fn remove_all_pellets(&mut self) {
+        for (x, y) in self.pellet_coords.clone() {
+            self.set(x as u32, y as u32, Tile::Empty);
+        }
         self.pellets = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(map: &[&str]) -> Vec<Vec<char>> {
+        map.iter().map(|row| row.chars().collect()).collect()
+    }
+
+    #[test]
+    fn test_parse_reads_start_markers() {
+        let map = Map::parse(rows(&["#######", "#S.BP.#", "#.IC.H#", "#######"])).unwrap();
+        assert_eq!(map.player_start(), (1, 1));
+        assert_eq!(map.ghost_starts(), [(3, 1), (4, 1), (2, 2), (3, 2)]);
+        assert!(matches!(map.get(1, 1), Some(Tile::Empty)));
+        assert!(matches!(map.get(3, 1), Some(Tile::Empty)));
+        assert_eq!(map.pellets(), 4);
+    }
+
+    #[test]
+    fn test_parse_derives_ghost_targets() {
+        let tiles = [
+            "#########",
+            "#S..B...#",
+            "#..#HH#.#",
+            "#..#HHH.#",
+            "#.P#HIC.#",
+            "#########",
+        ];
+        let map = Map::parse(rows(&tiles)).unwrap();
+        assert_eq!(map.scatter_targets(), [(6, -2), (2, -2), (8, 6), (0, 6)]);
+        assert_eq!(map.house_door(), (4, 1));
+        assert_eq!(map.house_respawn(), (5, 3));
+        assert_eq!(map.fruit_tile(), (1, 1));
+
+        let mut with_options = tiles.to_vec();
+        with_options.extend([
+            "@door=7,3",
+            "@scatter.inky=-1,3",
+            "@respawn=4,4",
+            "@fruit=2,3",
+        ]);
+        let map = Map::parse(rows(&with_options)).unwrap();
+        assert_eq!(map.house_door(), (7, 3));
+        assert_eq!(map.house_respawn(), (4, 4));
+        assert_eq!(map.fruit_tile(), (2, 3));
+        assert_eq!(map.scatter_targets(), [(6, -2), (2, -2), (-1, 3), (0, 6)]);
+
+        let err = |option: &str| {
+            let mut with_option = tiles.to_vec();
+            with_option.push(option);
+            Map::parse(rows(&with_option)).err().unwrap().to_string()
+        };
+        assert_eq!(err("@door=up"), "Invalid map option '@door=up' at row 6.");
+        assert_eq!(
+            err("@respawn=3,2"),
+            "Invalid map option '@respawn=3,2' at row 6."
+        );
+        assert_eq!(
+            err("@fruit=9,1"),
+            "Invalid map option '@fruit=9,1' at row 6."
+        );
+    }
+
+    #[test]
+    fn test_parse_finds_tunnels() {
+        let map = Map::parse(rows(&[
+            "##########",
+            "##S.BPIC##",
+            "  . #H#   ",
+            "##########",
+        ]))
+        .unwrap();
+        let tunnels: Vec<_> = (0..10).filter(|x| map.is_tunnel(*x, 2)).collect();
+        assert_eq!(tunnels, [0, 1, 8, 9]);
+        assert!(!map.is_tunnel(0, 1));
+        assert!(!map.is_tunnel(-1, 2));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_parse_rejects_malformed_maps() {
+        let err = |map: &[&str]| Map::parse(rows(map)).err().unwrap();
+        assert_eq!(err(&[]), MapError::Empty);
+        assert_eq!(err(&["#####", "#SB", "#####"]), MapError::RowLength { row: 1, len: 3, expected: 5 });
+        assert_eq!(err(&["#####", "#S?H#", "#####"]), MapError::UnknownChar { row: 1, col: 2, c: '?' });
+        assert_eq!(err(&["#####", "#.BP#", "#ICH#", "#####"]), MapError::MissingStart('S'));
+        assert_eq!(err(&["#####", "#SBP#", "#IHH#", "#####"]), MapError::MissingStart('C'));
+        assert_eq!(err(&["#####", "#SBP#", "#ICB#", "#####"]), MapError::DuplicateStart { row: 2, col: 3, c: 'B' });
+        assert_eq!(
+            err(&["#####", "#SBP#", "#ICB#", "#####"]).to_string(),
+            "Second start 'B' at row 2, column 3, there can only be one."
+        );
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_parse_checks_the_map_can_be_played() {
+        let err = |map: &[&str]| Map::parse(rows(map)).err().unwrap();
+        // Valid, with a tunnel in the middle row.
+        assert!(Map::parse(rows(&["######", "#SBPH#", "..IC..", "######"])).is_ok());
+        assert_eq!(err(&["######", "#SBPH#", "#.IC..", "######"]), MapError::OneSidedTunnel { row: 2 });
+        assert_eq!(err(&["##.###", "#SBPH#", "#.IC.#", "######"]), MapError::OpenEdge { row: 0, col: 2 });
+        assert_eq!(err(&["######", "#SBP.#", "#.IC.#", "######"]), MapError::NoGhostHouse);
+        assert_eq!(err(&["######", "#SBPH#", "#.IC..", "######"]).to_string(), "Row 2 is open on one side only.");
+        assert_eq!(err(&["#######", "#SBP#H#", "#.IC###", "#######"]), MapError::GhostHouseClosed);
+        assert_eq!(err(&["#######", "#SBP#.#", "#.ICH##", "#######"]), MapError::UnreachablePellet { row: 1, col: 5 });
+        assert_eq!(
+            err(&["#######", "#SBP#X#", "#.ICH##", "#######"]).to_string(),
+            "Pellet at row 1, column 5 cannot be reached from the start."
+        );
+    }
+}
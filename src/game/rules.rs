@@ -0,0 +1,249 @@
+//! The numbers that tune the game: scores, timers and lives.
+//!
+//! A rules file has one `key=value` line per rule, rules that are not in the file keep
+//! their default. Campaign levels can override them with the same keys.
+
+use std::convert::TryFrom;
+
+use super::brain::{Brain, BrainKind};
+use crate::sim::FileLoader;
+
+/// Highest `pacman_speed` and `ghost_speed`, ten times the arcade speeds.
+pub const MAX_SPEED: u32 = 1000;
+
+/// Highest `elroy_pellets`, more than any level has pellets.
+pub const MAX_ELROY_PELLETS: u32 = 1000;
+
+/// Keys of the brains of Blinky, Pinky, Inky and Clyde.
+const BRAIN_KEYS: [&str; 4] = ["blinky_brain", "pinky_brain", "inky_brain", "clyde_brain"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rules {
+    /// Lives at the start of the game, only used from the rules of the first level.
+    pub lives: u8,
+    /// Score that earns one extra life, 0 for none.
+    pub extra_life_score: u32,
+    pub score_pellet: u32,
+    pub score_power_up: u32,
+    pub score_ghost: u32,
+    /// Speed of Pac-Man and of the ghosts in percent of the arcade speeds for the level.
+    pub pacman_speed: u32,
+    pub ghost_speed: u32,
+    /// Dots Pinky, Inky and Clyde count before they leave the house, Blinky leaves right
+    /// away.
+    pub pinky_dots: u32,
+    pub inky_dots: u32,
+    pub clyde_dots: u32,
+    /// Pellets left when Blinky becomes Cruise Elroy in percent of the arcade game, 0 for
+    /// never.
+    pub elroy_pellets: u32,
+    /// Ticks without eating a dot after which the next ghost leaves the house, 0 for never.
+    pub idle_release_ticks: u16,
+    /// The brain of every ghost, in the order Blinky, Pinky, Inky, Clyde.
+    pub brains: [Brain; 4],
+    /// Ticks the ghosts stay frightened after a power up.
+    pub frightened_ticks: u16,
+    /// Pellets eaten in a level before the first and the second bonus fruit appear.
+    pub first_fruit_pellets: u32,
+    pub second_fruit_pellets: u32,
+    /// Ticks a bonus fruit stays before it disappears.
+    pub fruit_ticks: u16,
+    /// Ticks of the pause before playing, after dying and after clearing a level.
+    pub ready_ticks: u16,
+    pub dying_ticks: u16,
+    pub level_clear_ticks: u16,
+    /// Game ticks in a second, used to turn the timings of the arcade game into ticks.
+    pub ticks_per_second: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            lives: 5,
+            extra_life_score: 10000,
+            score_pellet: 10,
+            score_power_up: 50,
+            score_ghost: 200,
+            pacman_speed: 100,
+            ghost_speed: 100,
+            pinky_dots: 0,
+            inky_dots: 30,
+            clyde_dots: 60,
+            idle_release_ticks: 24,
+            elroy_pellets: 100,
+            brains: [
+                Brain::Classic(BrainKind::Blinky),
+                Brain::Classic(BrainKind::Pinky),
+                Brain::Classic(BrainKind::Inky),
+                Brain::Classic(BrainKind::Clyde),
+            ],
+            frightened_ticks: 30,
+            first_fruit_pellets: 70,
+            second_fruit_pellets: 170,
+            fruit_ticks: 60,
+            ready_ticks: 12,
+            dying_ticks: 6,
+            level_clear_ticks: 12,
+            ticks_per_second: 6,
+        }
+    }
+}
+
+impl Rules {
+    /// All rules as `(key, value)`, in the order they are written.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let numbers = [
+            ("lives", self.lives.into()),
+            ("extra_life_score", self.extra_life_score),
+            ("score_pellet", self.score_pellet),
+            ("score_power_up", self.score_power_up),
+            ("score_ghost", self.score_ghost),
+            ("pacman_speed", self.pacman_speed),
+            ("ghost_speed", self.ghost_speed),
+            ("pinky_dots", self.pinky_dots),
+            ("inky_dots", self.inky_dots),
+            ("clyde_dots", self.clyde_dots),
+            ("idle_release_ticks", self.idle_release_ticks.into()),
+            ("elroy_pellets", self.elroy_pellets),
+            ("frightened_ticks", self.frightened_ticks.into()),
+            ("first_fruit_pellets", self.first_fruit_pellets),
+            ("second_fruit_pellets", self.second_fruit_pellets),
+            ("fruit_ticks", self.fruit_ticks.into()),
+            ("ready_ticks", self.ready_ticks.into()),
+            ("dying_ticks", self.dying_ticks.into()),
+            ("level_clear_ticks", self.level_clear_ticks.into()),
+            ("ticks_per_second", self.ticks_per_second),
+        ];
+        let mut entries: Vec<_> = numbers
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect();
+        for (key, brain) in BRAIN_KEYS.iter().zip(self.brains.iter()) {
+            entries.push((key, brain.to_string()));
+        }
+        entries
+    }
+
+    /// Set the rule `key` from its text value.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let bad_value = || format!("Invalid value '{}' for '{}'.", value, key);
+        if let Some(i) = BRAIN_KEYS.iter().position(|k| *k == key) {
+            self.brains[i] = Brain::from_str(value).ok_or_else(bad_value)?;
+            return Ok(());
+        }
+        let n = value.parse::<u32>().map_err(|_| bad_value())?;
+        let small = |n| u16::try_from(n).map_err(|_| bad_value());
+        let tiny = |n| u8::try_from(n).map_err(|_| bad_value());
+        match key {
+            "lives" if n > 0 => self.lives = tiny(n)?,
+            "lives" => return Err(bad_value()),
+            "extra_life_score" => self.extra_life_score = n,
+            "score_pellet" => self.score_pellet = n,
+            "score_power_up" => self.score_power_up = n,
+            "score_ghost" => self.score_ghost = n,
+            "pacman_speed" if n <= MAX_SPEED => self.pacman_speed = n,
+            "ghost_speed" if n <= MAX_SPEED => self.ghost_speed = n,
+            "pacman_speed" | "ghost_speed" => return Err(bad_value()),
+            "pinky_dots" => self.pinky_dots = n,
+            "inky_dots" => self.inky_dots = n,
+            "clyde_dots" => self.clyde_dots = n,
+            "idle_release_ticks" => self.idle_release_ticks = small(n)?,
+            "elroy_pellets" if n <= MAX_ELROY_PELLETS => self.elroy_pellets = n,
+            "elroy_pellets" => return Err(bad_value()),
+            "frightened_ticks" => self.frightened_ticks = small(n)?,
+            "first_fruit_pellets" => self.first_fruit_pellets = n,
+            "second_fruit_pellets" => self.second_fruit_pellets = n,
+            "fruit_ticks" => self.fruit_ticks = small(n)?,
+            "ready_ticks" => self.ready_ticks = small(n)?,
+            "dying_ticks" => self.dying_ticks = small(n)?,
+            "level_clear_ticks" => self.level_clear_ticks = small(n)?,
+            "ticks_per_second" if n > 0 => self.ticks_per_second = n,
+            "ticks_per_second" => return Err(bad_value()),
+            _ => return Err(format!("Unknown rule '{}'.", key)),
+        }
+        Ok(())
+    }
+
+    pub fn parse(rows: &[Vec<char>]) -> Result<Self, String> {
+        let mut rules = Rules::default();
+        for (i, row) in rows.iter().enumerate() {
+            let line: String = row.iter().collect();
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("Line {}: {}", i + 1, msg);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| err("Expected 'key=value'.".to_string()))?;
+            rules.set(key.trim(), value.trim()).map_err(err)?;
+        }
+        Ok(rules)
+    }
+
+    pub fn load<L: FileLoader>(loader: &mut L, filename: &str) -> Result<Self, String> {
+        let rows = loader.load_file(filename).map_err(|e| e.to_string())?;
+        Rules::parse(&rows).map_err(|e| format!("Invalid rules '{}': {}", filename, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(lines: &[&str]) -> Vec<Vec<char>> {
+        lines.iter().map(|line| line.chars().collect()).collect()
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let mut brains = Rules::default().brains;
+        brains[2] = Brain::Tree("trees/shy.txt".to_string());
+        brains[3] = Brain::Classic(BrainKind::Blinky);
+        let rules = Rules::parse(&rows(&[
+            "# Harder",
+            "lives = 3",
+            "",
+            "score_ghost=300",
+            "inky_brain=tree:trees/shy.txt",
+            "clyde_brain=blinky",
+        ]))
+        .unwrap();
+        assert_eq!(
+            rules,
+            Rules {
+                lives: 3,
+                score_ghost: 300,
+                brains,
+                ..Rules::default()
+            }
+        );
+
+        let mut written = Rules::default();
+        for (key, value) in rules.entries().iter() {
+            written.set(key, value).unwrap();
+        }
+        assert_eq!(written, rules);
+
+        let err = |lines: &[&str]| Rules::parse(&rows(lines)).unwrap_err();
+        assert_eq!(err(&["lives"]), "Line 1: Expected 'key=value'.");
+        assert_eq!(
+            err(&["lives=300"]),
+            "Line 1: Invalid value '300' for 'lives'."
+        );
+        assert_eq!(err(&["#", "speed=3"]), "Line 2: Unknown rule 'speed'.");
+        assert_eq!(err(&["lives=0"]), "Line 1: Invalid value '0' for 'lives'.");
+        assert_eq!(
+            err(&["ghost_speed=1001"]),
+            "Line 1: Invalid value '1001' for 'ghost_speed'."
+        );
+        assert_eq!(
+            err(&["elroy_pellets=300000000"]),
+            "Line 1: Invalid value '300000000' for 'elroy_pellets'."
+        );
+        assert_eq!(
+            err(&["inky_brain=tree:"]),
+            "Line 1: Invalid value 'tree:' for 'inky_brain'."
+        );
+    }
+}
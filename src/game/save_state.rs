@@ -0,0 +1,282 @@
+//! Save states: the complete game written as `key=value` lines, so a game can be
+//! continued or replayed from any point.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+
+use super::{Direction, Game, GamePhase, Ghosts};
+use crate::sim::{stable_hash, Simulator};
+
+/// Version of the save state format, bump this when the format changes.
+pub const SAVE_STATE_VERSION: u32 = 6;
+
+pub struct StateWriter {
+    out: String,
+}
+
+impl StateWriter {
+    fn new() -> Self {
+        StateWriter { out: String::new() }
+    }
+
+    pub fn field<T: Display>(&mut self, key: &str, value: T) {
+        writeln!(self.out, "{}={}", key, value).unwrap();
+    }
+
+    pub fn pos(&mut self, key: &str, pos: (i32, i32)) {
+        self.field(key, format!("{},{}", pos.0, pos.1));
+    }
+}
+
+pub struct StateReader {
+    fields: HashMap<String, String>,
+}
+
+impl StateReader {
+    fn parse(state: &str) -> Result<Self, String> {
+        let mut fields = HashMap::new();
+        for (i, line) in state.lines().enumerate() {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Line {} of save state is not 'key=value'.", i + 1))?;
+            fields.insert(key.to_string(), value.to_string());
+        }
+        Ok(StateReader { fields })
+    }
+
+    pub fn str(&self, key: &str) -> Result<&str, String> {
+        self.fields
+            .get(key)
+            .map(|value| value.as_str())
+            .ok_or_else(|| format!("Save state is missing '{}'.", key))
+    }
+
+    pub fn bad_value(&self, key: &str) -> String {
+        format!(
+            "Invalid value '{}' for '{}' in save state.",
+            self.fields.get(key).map(|v| v.as_str()).unwrap_or(""),
+            key
+        )
+    }
+
+    pub fn field<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        self.str(key)?.parse::<T>().map_err(|_| self.bad_value(key))
+    }
+
+    pub fn char(&self, key: &str) -> Result<char, String> {
+        let mut chars = self.str(key)?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(self.bad_value(key)),
+        }
+    }
+
+    pub fn pos(&self, key: &str) -> Result<(i32, i32), String> {
+        self.str(key)?
+            .split_once(',')
+            .and_then(|(x, y)| Some((x.parse::<i32>().ok()?, y.parse::<i32>().ok()?)))
+            .ok_or_else(|| self.bad_value(key))
+    }
+}
+
+impl<RG: Simulator> Game<RG> {
+    /// Write the complete game state, including the random generator, as text.
+    pub fn save_state(&self) -> String {
+        let mut w = StateWriter::new();
+        w.field("version", SAVE_STATE_VERSION);
+        w.field("map_hash", format!("{:016x}", self.map.layout_hash()));
+        w.field("lives", self.lives);
+        w.field("extra_life_awarded", self.extra_life_awarded);
+        w.field("score", self.score);
+        w.field("level", self.level);
+        w.field("phase", self.phase.as_str());
+        w.field("phase_timer", self.phase_timer);
+        w.pos("player", (self.x, self.y));
+        w.field("direction", char::from(self.direction));
+        w.field("direction_intent", char::from(self.direction_intent));
+        w.field("pacman_progress", self.pacman_progress);
+        w.field("pacman_eating", self.pacman_eating);
+        w.field("ticks", self.ticks);
+        w.field("paused", self.paused);
+        w.field("fruits_spawned", self.fruits_spawned);
+        w.field("fruit_timer", self.fruit_timer);
+        w.field("rng", self.rg.state());
+        self.ghosts.save(&mut w);
+        self.map.save(&mut w);
+        w.out
+    }
+
+    /// Restore a state written by [`Game::save_state`]. The game must have been created
+    /// with the same map or campaign. On error the game is left unchanged.
+    pub fn load_state(&mut self, state: &str) -> Result<(), String> {
+        let r = StateReader::parse(state)?;
+        let version = r.field::<u32>("version")?;
+        if version != SAVE_STATE_VERSION {
+            return Err(format!(
+                "Unsupported save state version {}, expected {}.",
+                version, SAVE_STATE_VERSION
+            ));
+        }
+        // Levels of a campaign can have different maps.
+        let level = r.field::<usize>("level")?;
+        if level == 0 {
+            return Err(r.bad_value("level"));
+        }
+        let level_map = &self.maps[self.map_index(level)];
+        let map_hash =
+            u64::from_str_radix(r.str("map_hash")?, 16).map_err(|_| r.bad_value("map_hash"))?;
+        if map_hash != level_map.layout_hash() {
+            return Err(format!(
+                "Save state was made on a different map (hash {:016x}), but '{}' has hash {:016x}.",
+                map_hash,
+                self.campaign.levels[self.map_index(level)].map_file,
+                level_map.layout_hash()
+            ));
+        }
+
+        let direction = |key| Direction::try_from(r.char(key)?).map_err(|_| r.bad_value(key));
+        let (x, y) = r.pos("player")?;
+        let lives = r.field("lives")?;
+        let extra_life_awarded = r.field("extra_life_awarded")?;
+        let score = r.field("score")?;
+        let phase = GamePhase::from_str(r.str("phase")?).ok_or_else(|| r.bad_value("phase"))?;
+        let phase_timer = r.field("phase_timer")?;
+        let direction_value = direction("direction")?;
+        let direction_intent = direction("direction_intent")?;
+        let pacman_progress = r.field("pacman_progress")?;
+        let pacman_eating = r.field("pacman_eating")?;
+        let ticks = r.field("ticks")?;
+        let paused = r.field("paused")?;
+        let fruits_spawned = r.field("fruits_spawned")?;
+        let fruit_timer = r.field("fruit_timer")?;
+        let rng = r.field("rng")?;
+        let rules = &self.campaign.levels[self.map_index(level)].rules;
+        let brains = &self.brains[self.map_index(level)];
+        let ghosts = Ghosts::new(level_map, rules, brains, level).load(level_map, &r)?;
+        let mut map = level_map.clone();
+        map.load(&r)?;
+
+        self.lives = lives;
+        self.extra_life_awarded = extra_life_awarded;
+        self.score = score;
+        self.level = level;
+        self.phase = phase;
+        self.phase_timer = phase_timer;
+        self.x = x;
+        self.y = y;
+        self.direction = direction_value;
+        self.direction_intent = direction_intent;
+        self.pacman_progress = pacman_progress;
+        self.pacman_eating = pacman_eating;
+        self.ticks = ticks;
+        self.paused = paused;
+        self.fruits_spawned = fruits_spawned;
+        self.fruit_timer = fruit_timer;
+        self.rg.set_state(rng);
+        self.ghosts = ghosts;
+        self.map = map;
+        Ok(())
+    }
+
+    /// Restore a save state from a file, loaded through the simulator. The hash of its
+    /// contents is kept as [`Game::initial_state_hash`].
+    pub fn load_state_file(&mut self, filename: &str) -> Result<(), String> {
+        let rows = self.rg.load_file(filename).map_err(|e| e.to_string())?;
+        let state = rows
+            .iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.load_state(&state)
+            .map_err(|e| format!("Invalid save state '{}': {}", filename, e))?;
+        self.initial_state_hash = Some(stable_hash(&rows));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{new_game, MAP};
+    use crate::game::Input;
+    use crate::sim::{MemoryLoader, Sim};
+
+    #[test]
+    fn test_save_and_load_state() {
+        let mut game = new_game(1);
+        for (i, input) in [Input::Up, Input::Left, Input::Up, Input::Right, Input::Down]
+            .iter()
+            .enumerate()
+        {
+            game.input(*input);
+            for _ in 0..(3 + i) {
+                game.update();
+            }
+        }
+
+        // A game with a different seed ends up in exactly the same state after loading.
+        let mut restored = new_game(2);
+        restored.load_state(&game.save_state()).unwrap();
+        assert_eq!(restored.save_state(), game.save_state());
+        assert_eq!(restored.state_hash(), game.state_hash());
+        for _ in 0..50 {
+            game.update();
+            restored.update();
+            assert_eq!(restored.state_hash(), game.state_hash());
+        }
+
+        // Loading from a file keeps the hash of the file for recordings.
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", MAP);
+        loader.insert("save.txt", &game.save_state());
+        let mut restored = Game::new(game.params().clone(), Sim::new(2, loader)).unwrap();
+        assert_eq!(restored.initial_state_hash(), None);
+        restored.load_state_file("save.txt").unwrap();
+        assert_eq!(restored.state_hash(), game.state_hash());
+        assert!(restored.initial_state_hash().is_some());
+    }
+
+    #[test]
+    fn test_load_state_errors() {
+        let game = new_game(1);
+        let state = game.save_state();
+
+        let mut other = new_game(1);
+        other.update();
+        let hash = other.state_hash();
+        let load_err = |other: &mut Game<_>, state: String| other.load_state(&state).unwrap_err();
+        assert_eq!(
+            load_err(&mut other, state.replace("score=0", "score=-1")),
+            "Invalid value '-1' for 'score' in save state."
+        );
+        assert_eq!(
+            load_err(&mut other, state.replace("lives=5\n", "")),
+            "Save state is missing 'lives'."
+        );
+        assert_eq!(
+            load_err(&mut other, state.replace("version=6", "version=7")),
+            "Unsupported save state version 7, expected 6."
+        );
+        assert!(load_err(&mut other, state.replace("map.1=#.", "map.1=##")).contains("map.1"));
+        assert_eq!(
+            load_err(&mut other, state.replace("mode_timer=42", "mode_timer=0")),
+            "Invalid value '0' for 'mode_timer' in save state."
+        );
+        assert_eq!(
+            load_err(&mut other, state.replace("mode_phase=0", "mode_phase=8")),
+            "Invalid value '8' for 'mode_phase' in save state."
+        );
+        let pos = state
+            .lines()
+            .find(|line| line.starts_with("ghost.0.pos="))
+            .unwrap();
+        assert_eq!(
+            load_err(&mut other, state.replace(pos, "ghost.0.pos=-1,3")),
+            "Invalid value '-1,3' for 'ghost.0.pos' in save state."
+        );
+        // Failed loads leave the game untouched.
+        assert_eq!(other.state_hash(), hash);
+    }
+}
@@ -0,0 +1,106 @@
+//! How fast Pac-Man and the ghosts move.
+//!
+//! Speeds are in percent of one tile per tick. Every tick an entity adds its speed to its
+//! progress towards the next tile, and moves a tile for every [`TILE`] of progress. Only
+//! integers are used, so the same inputs always give the same game.
+
+use super::rules::{Rules, MAX_SPEED};
+
+/// Progress that makes up one tile.
+pub const TILE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Speeds {
+    pub pacman: u32,
+    /// Pac-Man while eating dots.
+    pub pacman_dots: u32,
+    /// Pac-Man while the ghosts are frightened.
+    pub pacman_frightened: u32,
+    pub pacman_frightened_dots: u32,
+    pub ghost: u32,
+    pub ghost_frightened: u32,
+    /// Ghosts in a tunnel, see [`Map::is_tunnel`](super::map::Map::is_tunnel).
+    pub ghost_tunnel: u32,
+    /// Blinky as Cruise Elroy, see [`Ghosts::elroy`](super::ghost::Ghosts::elroy).
+    pub elroy: u32,
+    pub elroy_2: u32,
+    /// The eyes of an eaten ghost on their way back to the house.
+    pub eyes: u32,
+}
+
+impl Speeds {
+    /// The speeds of the arcade game for `level`.
+    pub fn arcade(level: usize) -> Self {
+        let (pacman, ghost) = match level {
+            0 | 1 => ([80, 71, 90, 79], [75, 50, 40, 80, 85]),
+            2..=4 => ([90, 79, 95, 83], [85, 55, 45, 90, 95]),
+            5..=20 => ([100, 87, 100, 87], [95, 60, 50, 100, 105]),
+            _ => ([90, 79, 90, 79], [95, 60, 50, 100, 105]),
+        };
+        Speeds {
+            pacman: pacman[0],
+            pacman_dots: pacman[1],
+            pacman_frightened: pacman[2],
+            pacman_frightened_dots: pacman[3],
+            ghost: ghost[0],
+            ghost_frightened: ghost[1],
+            ghost_tunnel: ghost[2],
+            elroy: ghost[3],
+            elroy_2: ghost[4],
+            eyes: 2 * TILE,
+        }
+    }
+
+    /// The arcade speeds for `level`, scaled by the speed rules.
+    pub fn for_level(level: usize, rules: &Rules) -> Self {
+        let arcade = Speeds::arcade(level);
+        // Rules set in code can be above `MAX_SPEED`.
+        let scale = |speed: u32, percent: u32| {
+            let percent = percent.min(MAX_SPEED);
+            (u64::from(speed) * u64::from(percent) / 100) as u32
+        };
+        let pacman = |speed: u32| scale(speed, rules.pacman_speed);
+        let ghost = |speed: u32| scale(speed, rules.ghost_speed);
+        Speeds {
+            pacman: pacman(arcade.pacman),
+            pacman_dots: pacman(arcade.pacman_dots),
+            pacman_frightened: pacman(arcade.pacman_frightened),
+            pacman_frightened_dots: pacman(arcade.pacman_frightened_dots),
+            ghost: ghost(arcade.ghost),
+            ghost_frightened: ghost(arcade.ghost_frightened),
+            ghost_tunnel: ghost(arcade.ghost_tunnel),
+            elroy: ghost(arcade.elroy),
+            elroy_2: ghost(arcade.elroy_2),
+            eyes: arcade.eyes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speeds_for_level() {
+        let rules = Rules::default();
+        assert_eq!(Speeds::for_level(1, &rules), Speeds::arcade(1));
+        assert!(Speeds::arcade(5).pacman > Speeds::arcade(1).pacman);
+        assert_eq!(Speeds::arcade(21).pacman, 90);
+
+        let rules = Rules {
+            pacman_speed: 50,
+            ghost_speed: 200,
+            ..rules
+        };
+        let speeds = Speeds::for_level(1, &rules);
+        assert_eq!((speeds.pacman, speeds.pacman_dots), (40, 35));
+        assert_eq!((speeds.ghost, speeds.ghost_tunnel), (150, 80));
+        assert_eq!(speeds.eyes, 2 * TILE);
+
+        let rules = Rules {
+            pacman_speed: u32::MAX,
+            ..rules
+        };
+        assert_eq!(Speeds::for_level(1, &rules).pacman, 800);
+    }
+}
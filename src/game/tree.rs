@@ -0,0 +1,431 @@
+//! Behaviour trees that drive ghosts, as an alternative to the classic brains.
+//!
+//! A tree file has one node per line, children are indented below their parent:
+//!
+//! ```text
+//! # Flee when frightened, chase when close and otherwise think like Inky.
+//! selector
+//!   sequence
+//!     if frightened
+//!     flee
+//!   sequence
+//!     if near 6
+//!     chase
+//!   brain inky
+//! ```
+//!
+//! - `sequence` runs its children until one fails, `selector` until one succeeds.
+//! - `not` turns the result of its only child around, `succeed` always succeeds.
+//! - `if` checks a [`Condition`], every other line is an [`Action`] that picks the move.
+//!
+//! The tree is run from the root every time the ghost moves, and stops at the first action.
+//! Random conditions draw from the game's random generator, so replays stay exact.
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use super::brain::{BrainKind, GhostBrain, GhostView, Move};
+use super::ghost::GhostMode;
+use crate::sim::{FileLoader, RandGen};
+
+/// Highest number of tiles for `ambush` and coordinate for `move_to`, far outside any map.
+pub const MAX_TILES: i32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Condition {
+    /// The ghost is in this mode.
+    Mode(GhostMode),
+    /// Pac-Man is closer than this many tiles.
+    Near(u32),
+    /// Succeeds this percentage of the time.
+    Chance(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Head for Pac-Man.
+    Chase,
+    /// Head for the tile this many tiles in front of Pac-Man.
+    Ambush(i32),
+    /// Head for the ghost's corner.
+    Scatter,
+    /// Head for a tile.
+    MoveTo((i32, i32)),
+    Flee,
+    /// Move to a random neighbouring tile inside the house, stay put when there is none.
+    HouseMove,
+    /// Move like one of the classic brains.
+    Brain(BrainKind),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Sequence(Vec<Node>),
+    Selector(Vec<Node>),
+    Not(Box<Node>),
+    Succeed(Box<Node>),
+    Condition(Condition),
+    Action(Action),
+}
+
+impl Node {
+    /// Run the node, returns whether it succeeded. The first action that runs sets `picked`
+    /// and ends the run.
+    fn tick(&self, view: &GhostView, rg: &mut dyn RandGen, picked: &mut Option<Move>) -> bool {
+        match self {
+            Node::Sequence(children) => {
+                for child in children {
+                    if !child.tick(view, rg, picked) || picked.is_some() {
+                        return picked.is_some();
+                    }
+                }
+                true
+            }
+            Node::Selector(children) => {
+                for child in children {
+                    if child.tick(view, rg, picked) || picked.is_some() {
+                        return true;
+                    }
+                }
+                false
+            }
+            Node::Not(child) => !child.tick(view, rg, picked),
+            Node::Succeed(child) => {
+                child.tick(view, rg, picked);
+                true
+            }
+            Node::Condition(condition) => match *condition {
+                Condition::Mode(mode) => view.mode == mode,
+                Condition::Near(tiles) => {
+                    let me = view.ghost();
+                    let (x, y) = view.player_pos();
+                    let distance = (me.x() - x).pow(2) + (me.y() - y).pow(2);
+                    (distance as u64) < u64::from(tiles).pow(2)
+                }
+                Condition::Chance(percent) => (rg.rand() % 100) < percent as usize,
+            },
+            Node::Action(action) => {
+                let (x, y) = view.player_pos();
+                *picked = Some(match *action {
+                    Action::Chase => Move::Towards((x, y)),
+                    Action::Ambush(tiles) => {
+                        let v = view.player.2.to_vector();
+                        Move::Towards((x + v.0 * tiles, y + v.1 * tiles))
+                    }
+                    Action::Scatter => Move::Towards(view.scatter_target()),
+                    Action::MoveTo(target) => Move::Towards(target),
+                    Action::Flee => Move::Flee,
+                    Action::HouseMove => Move::HouseMove,
+                    Action::Brain(kind) => kind.brain().next_move(view, rg),
+                });
+                true
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tree {
+    root: Node,
+}
+
+impl GhostBrain for Tree {
+    /// A tree that runs no action makes the ghost wander off like a frightened one.
+    fn next_move(&self, view: &GhostView, rg: &mut dyn RandGen) -> Move {
+        let mut picked = None;
+        self.root.tick(view, rg, &mut picked);
+        picked.unwrap_or(Move::Flee)
+    }
+}
+
+/// A line of a tree file, without comments and blank lines.
+struct Line {
+    number: usize,
+    indent: usize,
+    words: Vec<String>,
+}
+
+impl Tree {
+    pub fn new(root: Node) -> Self {
+        Tree { root }
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn parse(rows: &[Vec<char>]) -> Result<Self, String> {
+        let mut lines = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let line: String = row.iter().collect();
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('\t') {
+                return Err(format!("Line {}: Indent with spaces, not tabs.", i + 1));
+            }
+            lines.push(Line {
+                number: i + 1,
+                indent: line.len() - trimmed.len(),
+                words: trimmed.split_whitespace().map(str::to_string).collect(),
+            });
+        }
+        if lines.is_empty() {
+            return Err("The tree has no nodes.".to_string());
+        }
+        let mut next = 0;
+        let root = parse_node(&lines, &mut next)?;
+        if let Some(line) = lines.get(next) {
+            return Err(format!(
+                "Line {}: A tree has only one root node.",
+                line.number
+            ));
+        }
+        Ok(Tree { root })
+    }
+
+    pub fn load<L: FileLoader>(loader: &mut L, filename: &str) -> Result<Self, String> {
+        let rows = loader.load_file(filename).map_err(|e| e.to_string())?;
+        Tree::parse(&rows).map_err(|e| format!("Invalid tree '{}': {}", filename, e))
+    }
+}
+
+/// Parse the node on `lines[*next]` and everything indented below it.
+fn parse_node(lines: &[Line], next: &mut usize) -> Result<Node, String> {
+    let line = &lines[*next];
+    *next += 1;
+    let mut children = Vec::new();
+    let child_indent = lines.get(*next).map(|l| l.indent).unwrap_or(0);
+    while let Some(child) = lines.get(*next).filter(|l| l.indent > line.indent) {
+        if child.indent != child_indent {
+            return Err(format!("Line {}: Unexpected indentation.", child.number));
+        }
+        children.push(parse_node(lines, next)?);
+    }
+    build_node(&line.words, children).map_err(|e| format!("Line {}: {}", line.number, e))
+}
+
+fn build_node(words: &[String], mut children: Vec<Node>) -> Result<Node, String> {
+    let name = words[0].as_str();
+    let args = &words[1..];
+    match name {
+        "sequence" | "selector" | "not" | "succeed" if !args.is_empty() => {
+            Err(format!("Too many arguments for '{}'.", name))
+        }
+        "sequence" | "selector" if children.is_empty() => {
+            Err(format!("'{}' needs at least one child.", name))
+        }
+        "sequence" => Ok(Node::Sequence(children)),
+        "selector" => Ok(Node::Selector(children)),
+        "not" | "succeed" if children.len() != 1 => {
+            Err(format!("'{}' needs exactly one child.", name))
+        }
+        "not" => Ok(Node::Not(Box::new(children.remove(0)))),
+        "succeed" => Ok(Node::Succeed(Box::new(children.remove(0)))),
+        _ if !children.is_empty() => Err(format!("'{}' cannot have children.", name)),
+        "if" => match args.split_first() {
+            Some((condition, args)) => parse_condition(condition, args).map(Node::Condition),
+            None => Err("Expected a condition after 'if'.".to_string()),
+        },
+        _ => parse_action(name, args).map(Node::Action),
+    }
+}
+
+/// The arguments of a node, checks that there are exactly as many as needed.
+struct Args<'a> {
+    name: &'a str,
+    args: &'a [String],
+}
+
+impl<'a> Args<'a> {
+    fn new(name: &'a str, args: &'a [String], count: usize) -> Result<Self, String> {
+        match args.len() {
+            n if n < count => Err(format!("Missing argument for '{}'.", name)),
+            n if n > count => Err(format!("Too many arguments for '{}'.", name)),
+            _ => Ok(Args { name, args }),
+        }
+    }
+
+    fn number(&self, i: usize) -> Result<u32, String> {
+        u32::from_str(&self.args[i])
+            .map_err(|_| format!("Invalid number '{}' for '{}'.", self.args[i], self.name))
+    }
+
+    /// A number of tiles or a coordinate, at most [`MAX_TILES`] so targets stay near the map.
+    fn tiles(&self, i: usize) -> Result<i32, String> {
+        match i32::try_from(self.number(i)?) {
+            Ok(tiles) if tiles <= MAX_TILES => Ok(tiles),
+            _ => Err(format!(
+                "Invalid number '{}' for '{}'.",
+                self.args[i], self.name
+            )),
+        }
+    }
+}
+
+fn parse_condition(name: &str, args: &[String]) -> Result<Condition, String> {
+    let count = match name {
+        "frightened" | "scatter" | "chase" => 0,
+        "near" | "chance" => 1,
+        _ => return Err(format!("Unknown condition '{}'.", name)),
+    };
+    let args = Args::new(name, args, count)?;
+    Ok(match name {
+        "frightened" => Condition::Mode(GhostMode::Frightened),
+        "scatter" => Condition::Mode(GhostMode::Scatter),
+        "chase" => Condition::Mode(GhostMode::Chase),
+        "near" => Condition::Near(args.number(0)?),
+        _ => match args.number(0)? {
+            percent if percent <= 100 => Condition::Chance(percent),
+            percent => return Err(format!("Invalid number '{}' for 'chance'.", percent)),
+        },
+    })
+}
+
+fn parse_action(name: &str, args: &[String]) -> Result<Action, String> {
+    let count = match name {
+        "chase" | "scatter" | "flee" | "house_move" => 0,
+        "ambush" | "brain" => 1,
+        "move_to" => 2,
+        _ => return Err(format!("Unknown node '{}'.", name)),
+    };
+    let args = Args::new(name, args, count)?;
+    Ok(match name {
+        "chase" => Action::Chase,
+        "scatter" => Action::Scatter,
+        "flee" => Action::Flee,
+        "house_move" => Action::HouseMove,
+        "ambush" => Action::Ambush(args.tiles(0)?),
+        "move_to" => Action::MoveTo((args.tiles(0)?, args.tiles(1)?)),
+        _ => Action::Brain(
+            BrainKind::from_str(&args.args[0])
+                .ok_or_else(|| format!("Unknown brain '{}'.", args.args[0]))?,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::brain::Brain;
+    use crate::game::testing::{instant_rules, MAP};
+    use crate::game::{Direction, Game, Parameters};
+    use crate::sim::{MemoryLoader, Sim};
+
+    fn rows(text: &str) -> Vec<Vec<char>> {
+        text.lines().map(|line| line.chars().collect()).collect()
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        let tree = Tree::parse(&rows(
+            "# comment\nselector\n  sequence\n    if near 3\n\n    chase\n  not\n    if chance 10\n  brain clyde",
+        ))
+        .unwrap();
+        assert_eq!(
+            tree.root(),
+            &Node::Selector(vec![
+                Node::Sequence(vec![
+                    Node::Condition(Condition::Near(3)),
+                    Node::Action(Action::Chase),
+                ]),
+                Node::Not(Box::new(Node::Condition(Condition::Chance(10)))),
+                Node::Action(Action::Brain(BrainKind::Clyde)),
+            ])
+        );
+
+        let err = |text: &str| Tree::parse(&rows(text)).unwrap_err();
+        assert_eq!(err("# nothing"), "The tree has no nodes.");
+        assert_eq!(err("chase\nflee"), "Line 2: A tree has only one root node.");
+        assert_eq!(
+            err("selector"),
+            "Line 1: 'selector' needs at least one child."
+        );
+        assert_eq!(
+            err("not\n  flee\n  chase"),
+            "Line 1: 'not' needs exactly one child."
+        );
+        assert_eq!(err("flee\n  chase"), "Line 1: 'flee' cannot have children.");
+        assert_eq!(
+            err("selector\n    flee\n  chase"),
+            "Line 3: Unexpected indentation."
+        );
+        assert_eq!(err("if hungry"), "Line 1: Unknown condition 'hungry'.");
+        assert_eq!(err("if near"), "Line 1: Missing argument for 'near'.");
+        assert_eq!(
+            err("move_to 1 2 3"),
+            "Line 1: Too many arguments for 'move_to'."
+        );
+        assert_eq!(
+            err("ambush far"),
+            "Line 1: Invalid number 'far' for 'ambush'."
+        );
+        assert_eq!(
+            err("ambush 4294967295"),
+            "Line 1: Invalid number '4294967295' for 'ambush'."
+        );
+        assert_eq!(
+            err("move_to 1 1001"),
+            "Line 1: Invalid number '1001' for 'move_to'."
+        );
+        assert_eq!(err("brain sue"), "Line 1: Unknown brain 'sue'.");
+    }
+
+    const TREE: &str = "selector\n  sequence\n    if chance 50\n    house_move\n  move_to 1 1";
+
+    fn tree_game(seed: u64) -> Game<Sim<MemoryLoader>> {
+        tree_game_with(seed, TREE)
+    }
+
+    /// A game where every ghost has `tree` for a brain.
+    fn tree_game_with(seed: u64, tree: &str) -> Game<Sim<MemoryLoader>> {
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", MAP);
+        loader.insert("tree.txt", tree);
+        let mut rules = instant_rules();
+        for brain in rules.brains.iter_mut() {
+            *brain = Brain::Tree("tree.txt".to_string());
+        }
+        let params = Parameters {
+            start_dir: Direction::Left,
+            map_file: "map.txt".to_string(),
+            campaign: None,
+            rules,
+        };
+        Game::new(params, Sim::new(seed, loader)).unwrap()
+    }
+
+    #[test]
+    fn test_trees_drive_ghosts() {
+        // Outside the house the ghosts stand still half of the time and otherwise head for
+        // the top left corner. The same seed gives the same game.
+        let mut game = tree_game(7);
+        let mut again = tree_game(7);
+        for _ in 0..100 {
+            game.tick();
+            again.tick();
+        }
+        assert_eq!(game.state_hash(), again.state_hash());
+        let outside = |game: &Game<_>| {
+            game.ghosts()
+                .iter()
+                .filter(|g| !game.map().is_house(g.x(), g.y()))
+                .count()
+        };
+        assert!(outside(&game) > 0);
+
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", MAP);
+        // Recordings notice when a tree changes.
+        assert!(game.trees_hash().is_some());
+        assert_ne!(
+            tree_game_with(7, &TREE.replace("50", "40")).trees_hash(),
+            game.trees_hash()
+        );
+
+        let mut params = tree_game(1).params().clone();
+        params.rules.brains[0] = Brain::Tree("missing.txt".to_string());
+        assert!(Game::new(params, Sim::new(1, loader)).is_err());
+    }
+}
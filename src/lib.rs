@@ -0,0 +1,8 @@
+//! The pacman game without any graphics: the game rules, the simulator it runs on and
+//! tools to record, replay and rewind games. The `pac` binary adds the window on top.
+
+pub mod env;
+pub mod game;
+pub mod replay;
+pub mod rewind;
+pub mod sim;
@@ -1,29 +1,34 @@
-mod game;
-mod sim;
 mod view;
 
 use std::convert::TryFrom;
 
 use glutin_window::GlutinWindow as Window;
 use opengl_graphics::{GlGraphics, OpenGL};
-use piston::event_loop::{EventSettings, Events};
+use piston::event_loop::{EventLoop, EventSettings, Events};
+use piston::input::keyboard::Key;
 use piston::input::{RenderEvent, ResizeEvent};
 use piston::window::WindowSettings;
 use piston::{Button, PressEvent, UpdateEvent};
 
 use clap::{Parser, ValueEnum};
 
-use crate::game::{Game, Parameters};
-use crate::sim::{FileLoader, RandGen, Simulator};
+use rust_gaem::game::{self, Game, Parameters};
+use rust_gaem::replay::Replay;
+use rust_gaem::rewind::Rewind;
+use rust_gaem::sim::{self, Sim, Simulator};
+
 use crate::view::View;
 
 const GL_VERSION: OpenGL = OpenGL::V4_5;
 /// Number of sequence the main loop should tick the game in gui mode.
 const UPDATE_HZ: u64 = 6;
+/// Number of frames between game state checksums in new recordings.
+const CHECKSUM_INTERVAL: u64 = 10;
+/// Number of frames to go back when pressing `B`.
+const REWIND_FRAMES: u64 = UPDATE_HZ;
 
 fn button_to_input(button: Button) -> game::Input {
     use game::Input;
-    use piston::input::keyboard::Key;
     use piston::input::Button::Keyboard;
 
     match button {
@@ -37,24 +42,43 @@ fn button_to_input(button: Button) -> game::Input {
     }
 }
 
-fn try_parse_recording(recording: sim::Recording) -> Result<Vec<(u64, game::Input)>, String> {
-    // None-empty.
-    let last_input = recording.last().ok_or("Empty recording.".to_string())?;
-
-    // Finite.
-    if last_input.1 != 'q' {
-        return Err("This recording will never quite.".to_string());
-    }
-
-    // Valid data.
-    let mut inputs = Vec::<(u64, game::Input)>::new();
-    inputs.reserve(recording.len());
-    for (count, char) in &recording.clone() {
-        let input = game::Input::try_from(*char)?;
-        inputs.push((*count, input));
+/// Header describing how the game was set up, stored at the top of a new recording.
+fn recording_header<RG: Simulator>(
+    game: &Game<RG>,
+    seed: u64,
+    initial_state: Option<(String, u64)>,
+) -> sim::RecordingHeader {
+    let params = game.params();
+    sim::RecordingHeader {
+        version: sim::RECORDING_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        map_file: game.campaign().levels[0].map_file.clone(),
+        map_hash: game.map().layout_hash(),
+        seed,
+        start_dir: params.start_dir.into(),
+        update_hz: UPDATE_HZ,
+        checksum_interval: CHECKSUM_INTERVAL,
+        initial_state,
+        campaign: params.campaign.clone().zip(game.campaign_hash()),
+        trees_hash: game.trees_hash(),
+        rules: params.rules.clone(),
     }
+}
 
-    Ok(inputs)
+/// Parameters to replay a recording with, `map_file` can override the recorded map.
+fn params_from_header(
+    header: &sim::RecordingHeader,
+    map_file: Option<String>,
+) -> Result<Parameters, String> {
+    Ok(Parameters {
+        start_dir: game::Direction::try_from(header.start_dir)?,
+        map_file: map_file.unwrap_or_else(|| header.map_file.clone()),
+        campaign: header
+            .campaign
+            .as_ref()
+            .map(|(campaign, _)| campaign.clone()),
+        rules: header.rules.clone(),
+    })
 }
 
 struct Buffer<T: Clone + Copy> {
@@ -66,10 +90,6 @@ impl<T: Clone + Copy> Buffer<T> {
         Self { x: None }
     }
 
-    fn is_empty(&self) -> bool {
-        self.x.is_none()
-    }
-
     fn push(&mut self, most_recent_input: T) {
         self.x = Some(most_recent_input);
     }
@@ -81,88 +101,23 @@ impl<T: Clone + Copy> Buffer<T> {
     }
 }
 
-#[derive(Debug, Default)]
-struct Sim1 {
-    x: usize,
-}
-
-impl RandGen for Sim1 {
-    fn rand(&mut self) -> usize {
-        self.x += 1;
-        self.x
-    }
-}
-
-impl FileLoader for Sim1 {
-    fn load_file(&mut self, _filename: &str) -> Vec<Vec<char>> {
-        // const MAP_STR: [&'static str; 31] = [
-        //     "############################",
-        //     "#............##............#",
-        //     "#.####.#####.##.#####.####.#",
-        //     "#X####.#####.##.#####.####X#",
-        //     "#.####.#####.##.#####.####.#",
-        //     "#..........................#",
-        //     "#.####.##.########.##.####.#",
-        //     "#.####.##.########.##.####.#",
-        //     "#......##....##....##......#",
-        //     "######.##### ## #####.######",
-        //     "######.##### ## #####.######",
-        //     "######.##          ##.######",
-        //     "######.## ###HH### ##.######",
-        //     "######.## #HHHHHH# ##.######",
-        //     "      .   #HHHHHH#   .      ",
-        //     "######.## #HHHHHH# ##.######",
-        //     "######.## ######## ##.######",
-        //     "######.##          ##.######",
-        //     "######.## ######## ##.######",
-        //     "######.## ######## ##.######",
-        //     "#............##............#",
-        //     "#.####.#####.##.#####.####.#",
-        //     "#.####.#####.##.#####.####.#",
-        //     "#X..##................##..X#",
-        //     "###.##.##.########.##.##.###",
-        //     "###.##.##.########.##.##.###",
-        //     "#......##....##....##......#",
-        //     "#.##########.##.##########.#",
-        //     "#.##########.##.##########.#",
-        //     "#..........................#",
-        //     "############################",
-        // ];
-        let rows = vec![
-            "############################",
-            "#................X.........#",
-            "#..........................#",
-            "#..........BP..............#",
-            "#..........IC..............#",
-            "#..............X.X.........#",
-            "#..........................#",
-            "#..........................#",
-            "#..........................#",
-            "############################",
-        ];
-        println!("num rows: {}", rows.len());
-        rows.iter()
-            .map(|x| {
-                let v = x.chars().collect();
-                return v;
-            })
-            .collect()
-    }
-}
-
-impl Simulator for Sim1 {}
-
+/// `replay_info` is the current frame and recording index, shown when replaying.
 fn maybe_render<RG: Simulator>(
     e: &piston::Event,
     game: &Game<RG>,
     gl: &mut GlGraphics,
     view: &mut View,
+    replay_info: Option<(u64, usize)>,
 ) {
+    view.update(game);
     // Render
     if let Some(r) = e.render_args() {
         gl.draw(r.viewport(), |c, g| {
             graphics::clear([0.0; 4], g);
-            view.draw(&game, &c, g);
+            view.draw(game, &c, g);
+            if let Some((frame, input_index)) = replay_info {
+                view.draw_replay_info(frame, input_index, game.map().width, &c, g);
+            }
         })
     }
     if let Some(r) = e.resize_args() {
@@ -175,8 +130,13 @@ fn maybe_render<RG: Simulator>(
     }
 }
 
-fn run<RG: Simulator>(events: &mut Events, game: &mut Game<RG>) -> sim::Recording {
-    let mut recording = sim::Recording::new();
+fn run<RG: Simulator>(
+    events: &mut Events,
+    game: &mut Game<RG>,
+    header: sim::RecordingHeader,
+    save_state_filepath: &str,
+) -> sim::Recording {
+    let mut recording = sim::Recording::new(header);
     const GL_VERSION: OpenGL = OpenGL::V4_5;
     let mut window: Window = WindowSettings::new("pacman-game", [750, 750])
         .graphics_api(GL_VERSION)
@@ -188,9 +148,33 @@ fn run<RG: Simulator>(events: &mut Events, game: &mut Game<RG>) -> sim::Recordin
 
     let mut frame_count: u64 = 0;
     let mut input_source = Buffer::<game::Input>::new();
+    let mut rewind = Rewind::default();
     while let Some(e) = events.next(&mut window) {
         // Input
         if let Some(button) = e.press_args() {
+            if button == Button::Keyboard(Key::S) {
+                // Saving is not a game input, so it does not end up in the recording.
+                match std::fs::write(save_state_filepath, game.save_state()) {
+                    Ok(()) => println!("[{}]-- saved state --", frame_count),
+                    Err(e) => eprintln!("ERROR: {}: {}", save_state_filepath, e),
+                }
+                continue;
+            }
+            if button == Button::Keyboard(Key::G) {
+                view.toggle_debug();
+                continue;
+            }
+            if button == Button::Keyboard(Key::B) {
+                // The recording continues from the earlier frame as if the rest never happened.
+                match rewind.step_back(game, frame_count, REWIND_FRAMES) {
+                    Ok(frame) => frame_count = frame,
+                    Err(e) => eprintln!("ERROR: {e}"),
+                }
+                recording.truncate(frame_count);
+                input_source.pop();
+                println!("[{}]-- rewind --", frame_count);
+                continue;
+            }
             println!("[{}]-- input --", frame_count);
             let input = button_to_input(button);
             // This will overwrite the previous input.
@@ -201,46 +185,57 @@ fn run<RG: Simulator>(events: &mut Events, game: &mut Game<RG>) -> sim::Recordin
 
         // Update
         if e.update_args().is_some() {
+            rewind.record_frame(frame_count, game);
             if let Some(input) = input_source.pop() {
                 recording.push((frame_count, input.into()));
+                rewind.record_input(frame_count, input);
                 if game.input(input) {
                     return recording;
                 }
             }
             println!("[{}]-- update --", frame_count);
             game.update();
+            let interval = recording.header.checksum_interval;
+            if interval != 0 && frame_count.is_multiple_of(interval) {
+                recording.push_checksum(frame_count, game.state_hash());
+            }
             frame_count += 1;
         }
-        maybe_render(&e, &game, &mut gl, &mut view);
+        maybe_render(&e, game, &mut gl, &mut view, None);
     }
-    return recording;
+    recording
 }
 
 fn run_from_recording_nogui<RG: Simulator>(
     game: &mut Game<RG>,
     recording: sim::Recording,
 ) -> Result<(), String> {
-    let inputs = try_parse_recording(recording)?;
-    let max_frame_count = inputs.last().unwrap().0;
-
-    let mut idx = 0;
-    for frame_count in 0..max_frame_count {
-        // I think this cannot go out of bounds because of the validation for 'q'
-        // in `try_parse_recording`.
-        assert!(idx < inputs.len());
+    Replay::new(recording, game)?.run_to_end(game)
+}
 
-        if inputs[idx].0 == frame_count {
-            if game.input(inputs[idx].1) {
-                return Ok(());
-            }
-            idx += 1;
-        }
-        game.update();
+fn key_to_digit(key: Key) -> Option<char> {
+    match key {
+        Key::D0 | Key::NumPad0 => Some('0'),
+        Key::D1 | Key::NumPad1 => Some('1'),
+        Key::D2 | Key::NumPad2 => Some('2'),
+        Key::D3 | Key::NumPad3 => Some('3'),
+        Key::D4 | Key::NumPad4 => Some('4'),
+        Key::D5 | Key::NumPad5 => Some('5'),
+        Key::D6 | Key::NumPad6 => Some('6'),
+        Key::D7 | Key::NumPad7 => Some('7'),
+        Key::D8 | Key::NumPad8 => Some('8'),
+        Key::D9 | Key::NumPad9 => Some('9'),
+        _ => None,
     }
-
-    Ok(())
 }
 
+/// Replay with the controls:
+/// - `Space` pause or continue
+/// - `Right` or `.` step one frame forward, `Left` or `,` one frame back
+/// - `B` go back one second
+/// - `G` show or hide when the ghosts leave their house
+/// - `+` and `-` double or halve the speed
+/// - type a frame number and press `Enter` to jump to it
 fn run_from_recoding<RG: Simulator>(
     events: &mut Events,
     game: &mut Game<RG>,
@@ -254,32 +249,75 @@ fn run_from_recoding<RG: Simulator>(
     let mut gl = GlGraphics::new(GL_VERSION);
     let mut view = View::new();
 
-    let inputs = try_parse_recording(recording)?;
-    let mut idx_input: usize = 0;
-
-    let mut frame_count: u64 = 0;
-    let mut input_source = Buffer::<game::Input>::new();
+    let mut replay = Replay::new(recording, game)?;
+    println!("-- replaying {} frames --", replay.last_frame());
+    let base_ups = events.get_event_settings().ups;
+    let mut speed = 1.0;
+    let mut paused = false;
+    let mut seek_target = String::new();
 
     while let Some(e) = events.next(&mut window) {
-        // I think this cannot go out of bounds because of the validation for 'q'
-        // in `try_parse_recording`.
-        assert!(idx_input < inputs.len());
-        // For the same reson we cannot run past the largest frame count.
-        assert!(frame_count < inputs.last().unwrap().0);
-
-        if input_source.is_empty() && inputs[idx_input].0 == frame_count {
-            input_source.push(inputs[idx_input].1);
-            idx_input += 1;
-        }
-
-        if let Some(_) = e.update_args() {
-            if game.input(input_source.pop().unwrap_or(game::Input::None)) {
+        if let Some(Button::Keyboard(key)) = e.press_args() {
+            let frame = replay.frame();
+            let end_reached = match key {
+                Key::Space => {
+                    paused = !paused;
+                    println!(
+                        "[{}]-- replay {} --",
+                        frame,
+                        if paused { "paused" } else { "continued" }
+                    );
+                    false
+                }
+                Key::Right | Key::Period => replay.step(game)?,
+                Key::Left | Key::Comma => replay.seek(game, frame.saturating_sub(1))?,
+                Key::B => replay.seek(game, frame.saturating_sub(REWIND_FRAMES))?,
+                Key::G => {
+                    view.toggle_debug();
+                    false
+                }
+                Key::Equals | Key::Plus | Key::NumPadPlus | Key::Minus | Key::NumPadMinus => {
+                    let faster = matches!(key, Key::Equals | Key::Plus | Key::NumPadPlus);
+                    speed = if faster { speed * 2.0 } else { speed / 2.0 };
+                    events.set_ups(((base_ups as f64 * speed) as u64).max(1));
+                    println!("[{}]-- replay speed x{} --", frame, speed);
+                    false
+                }
+                Key::Backspace => {
+                    seek_target.pop();
+                    false
+                }
+                Key::Return | Key::NumPadEnter => match seek_target.parse::<u64>() {
+                    Ok(target) => {
+                        seek_target.clear();
+                        println!("[{}]-- jump to frame {} --", frame, target);
+                        replay.seek(game, target)?
+                    }
+                    Err(_) => false,
+                },
+                _ => {
+                    if let Some(digit) = key_to_digit(key) {
+                        seek_target.push(digit);
+                        println!("jump to frame: {}", seek_target);
+                    }
+                    false
+                }
+            };
+            if end_reached {
                 return Ok(());
             }
-            game.update();
-            frame_count += 1;
         }
-        maybe_render(&e, &game, &mut gl, &mut view);
+
+        if e.update_args().is_some() && !paused && replay.step(game)? {
+            return Ok(());
+        }
+        maybe_render(
+            &e,
+            game,
+            &mut gl,
+            &mut view,
+            Some((replay.frame(), replay.input_index())),
+        );
     }
 
     Ok(())
@@ -306,6 +344,47 @@ struct CliArgs {
 
     #[arg(long, default_value = "recording.game.txt")]
     recording_filepath: String,
+
+    /// Skip malformed lines in the recording instead of failing on the first one.
+    #[arg(long)]
+    lenient: bool,
+
+    /// Save state to start the game from. When replaying the save state stored in the
+    /// recording is used instead.
+    #[arg(long)]
+    load_state: Option<String>,
+
+    /// Where to write the save state when pressing `S` while playing.
+    #[arg(long, default_value = "save.state.txt")]
+    save_state_filepath: String,
+
+    /// Map file to play on. Defaults to `map.txt` when recording and to the recorded map
+    /// when replaying.
+    #[arg(long)]
+    map: Option<String>,
+
+    /// Campaign file with the map and settings of every level, see `campaigns/`. When
+    /// replaying the recorded campaign is used.
+    #[arg(long)]
+    campaign: Option<String>,
+
+    /// Rules file with the scores, timers and lives to play with, see `rules.txt`. When
+    /// replaying the recorded rules are used.
+    #[arg(long)]
+    rules: Option<String>,
+
+    /// Seed for the random number generator. When recording without a seed a new one is
+    /// picked. Ignored when replaying, the seed stored in the recording is used instead.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// Seed for a fresh recording when none is given on the command line.
+fn seed_from_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(sim::DEFAULT_SEED)
 }
 
 fn main() {
@@ -318,30 +397,82 @@ fn main() {
 
     let should_render = !args.nogui;
 
-    let state_init = Parameters {
-        start_pos: (26, 8),
-        start_dir: game::Direction::Left,
+    let exit_with_error = |e: String| -> ! {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    };
+
+    let recording = match args.mode {
+        AppMode::Replay => match sim::read_recording_from_file(
+            &args.recording_filepath,
+            if args.lenient {
+                sim::ParseMode::Lenient
+            } else {
+                sim::ParseMode::Strict
+            },
+        ) {
+            Ok(recording) => Some(recording),
+            Err(e) => exit_with_error(format!("{}: {}", args.recording_filepath, e)),
+        },
+        AppMode::Record => None,
+    };
+    let (state_init, seed, update_hz, initial_state) = match &recording {
+        Some(recording) => {
+            let header = &recording.header;
+            let params =
+                params_from_header(header, args.map.clone()).unwrap_or_else(|e| exit_with_error(e));
+            (
+                params,
+                header.seed,
+                header.update_hz,
+                header.initial_state.clone().map(|(filename, _)| filename),
+            )
+        }
+        None => {
+            let rules = match &args.rules {
+                Some(filename) => game::rules::Rules::load(&mut sim::DiskLoader, filename)
+                    .unwrap_or_else(|e| exit_with_error(e)),
+                None => game::rules::Rules::default(),
+            };
+            let params = Parameters {
+                start_dir: game::Direction::Left,
+                map_file: args.map.clone().unwrap_or("map.txt".to_string()),
+                campaign: args.campaign.clone(),
+                rules,
+            };
+            (
+                params,
+                args.seed.unwrap_or_else(seed_from_time),
+                UPDATE_HZ,
+                args.load_state.clone(),
+            )
+        }
     };
+    println!("seed: {}", seed);
 
-    let sim = Sim1::default();
-    let mut game = Game::new(state_init, sim);
+    let sim = Sim::new(seed, sim::DiskLoader);
+    let mut game = Game::new(state_init, sim).unwrap_or_else(|e| exit_with_error(e));
+    if let Some(initial_state) = &initial_state {
+        game.load_state_file(initial_state)
+            .unwrap_or_else(|e| exit_with_error(e));
+    }
     let mut settings = EventSettings::new();
     // settings.bench_mode = true;
-    settings.ups = (UPDATE_HZ as f64 * args.playback_speed) as u64;
+    settings.ups = (update_hz as f64 * args.playback_speed) as u64;
     let mut events = Events::new(settings);
 
     if args.mode == AppMode::Replay && should_render {
-        let recording = sim::read_recording_from_file(&args.recording_filepath).unwrap();
-        if let Err(e) = run_from_recoding(&mut events, &mut game, recording) {
+        if let Err(e) = run_from_recoding(&mut events, &mut game, recording.unwrap()) {
             eprintln!("ERROR: {e}");
         }
     } else if args.mode == AppMode::Replay && !should_render {
-        let recording = sim::read_recording_from_file(&args.recording_filepath).unwrap();
-        if let Err(e) = run_from_recording_nogui(&mut game, recording) {
+        if let Err(e) = run_from_recording_nogui(&mut game, recording.unwrap()) {
             eprintln!("ERROR: {e}");
         }
     } else if args.mode == AppMode::Record && should_render {
-        let recording = run(&mut events, &mut game);
+        let initial_state = initial_state.zip(game.initial_state_hash());
+        let header = recording_header(&game, seed, initial_state);
+        let recording = run(&mut events, &mut game, header, &args.save_state_filepath);
         sim::write_recording_to_file(&recording, &args.recording_filepath).unwrap();
     } else {
         panic!("Invalid options");
@@ -357,13 +488,41 @@ mod tests {
     #[test]
     fn run_example_recording() {
         // We can use snapshot testing here!
-        let sim = Sim1::default();
-        let state_init = Parameters {
-            start_pos: (26, 8),
-            start_dir: game::Direction::Left,
-        };
-        let mut game = Game::new(state_init, sim);
-        let recording = sim::read_recording_from_file("test_game_file.txt").unwrap();
+        let recording =
+            sim::read_recording_from_file("test_game_file.txt", sim::ParseMode::Strict).unwrap();
+        let sim = Sim::new(recording.header.seed, sim::DiskLoader);
+        let state_init = params_from_header(&recording.header, None).unwrap();
+        let mut game = Game::new(state_init, sim).unwrap();
         assert_eq!(run_from_recording_nogui(&mut game, recording), Ok(()));
     }
+
+    #[test]
+    fn replay_reports_first_diverging_frame() {
+        let mut recording =
+            sim::read_recording_from_file("test_game_file.txt", sim::ParseMode::Strict).unwrap();
+        let (frame, hash) = recording.checksums[3];
+        recording.checksums[3] = (frame, hash ^ 1);
+        let sim = Sim::new(recording.header.seed, sim::DiskLoader);
+        let state_init = params_from_header(&recording.header, None).unwrap();
+        let mut game = Game::new(state_init, sim).unwrap();
+        let result = run_from_recording_nogui(&mut game, recording);
+        assert!(result.unwrap_err().starts_with(&format!(
+            "Game state diverged from the recording at frame {}:",
+            frame
+        )));
+    }
+
+    #[test]
+    fn replay_on_wrong_map_fails() {
+        let recording =
+            sim::read_recording_from_file("test_game_file.txt", sim::ParseMode::Strict).unwrap();
+        let sim = Sim::new(recording.header.seed, sim::DiskLoader);
+        let state_init =
+            params_from_header(&recording.header, Some("maps/classic.txt".to_string())).unwrap();
+        let mut game = Game::new(state_init, sim).unwrap();
+        let result = run_from_recording_nogui(&mut game, recording);
+        assert!(result
+            .unwrap_err()
+            .starts_with("Recording was made on map 'map.txt'"));
+    }
 }
@@ -0,0 +1,265 @@
+//! Playing back a recording, frame by frame or by jumping to any frame.
+
+use std::convert::TryFrom;
+
+use crate::game::{Game, Input};
+use crate::rewind::Rewind;
+use crate::sim::{Recording, RecordingHeader, Simulator};
+
+fn try_parse_recording(recording: &Recording) -> Result<Vec<(u64, Input)>, String> {
+    let recording = &recording.inputs;
+    // Non-empty.
+    if recording.is_empty() {
+        return Err("Empty recording.".to_string());
+    }
+
+    // Valid data.
+    let mut inputs = Vec::<(u64, Input)>::with_capacity(recording.len());
+    for (count, char) in recording {
+        let input = Input::try_from(*char)?;
+        inputs.push((*count, input));
+    }
+
+    Ok(inputs)
+}
+
+/// Check that the game is set up the same way as when the recording was made.
+fn check_recording_header<RG: Simulator>(
+    header: &RecordingHeader,
+    game: &Game<RG>,
+) -> Result<(), String> {
+    if header.map_hash != game.map().layout_hash() {
+        return Err(format!(
+            "Recording was made on map '{}' (hash {:016x}), but '{}' has hash {:016x}.",
+            header.map_file,
+            header.map_hash,
+            game.params().map_file,
+            game.map().layout_hash()
+        ));
+    }
+    let describe = |what: &str, hash: Option<u64>| match hash {
+        Some(hash) => format!("{} with hash {:016x}", what, hash),
+        None => format!("no {}", what),
+    };
+    let recorded = header.campaign.as_ref().map(|(_, hash)| *hash);
+    if recorded != game.campaign_hash() {
+        return Err(format!(
+            "Recording was made with {}, but the game has {}.",
+            describe("campaign", recorded),
+            describe("campaign", game.campaign_hash())
+        ));
+    }
+    if header.trees_hash != game.trees_hash() {
+        return Err(format!(
+            "Recording was made with {}, but the game has {}.",
+            describe("behaviour trees", header.trees_hash),
+            describe("behaviour trees", game.trees_hash())
+        ));
+    }
+    let recorded = header.initial_state.as_ref().map(|(_, hash)| *hash);
+    if recorded != game.initial_state_hash() {
+        return Err(format!(
+            "Recording was started from {}, but the game has {}.",
+            describe("save state", recorded),
+            describe("save state", game.initial_state_hash())
+        ));
+    }
+    Ok(())
+}
+
+/// Compare the game state after the update of `frame` with the checksum recorded for it.
+fn check_state_hash<RG: Simulator>(
+    checksums: &[(u64, u64)],
+    frame: u64,
+    game: &Game<RG>,
+) -> Result<(), String> {
+    if let Ok(i) = checksums.binary_search_by_key(&frame, |(f, _)| *f) {
+        let expected = checksums[i].1;
+        let actual = game.state_hash();
+        if expected != actual {
+            return Err(format!(
+                "Game state diverged from the recording at frame {}: expected hash {:016x}, got {:016x}.",
+                frame, expected, actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub struct Replay {
+    inputs: Vec<(u64, Input)>,
+    checksums: Vec<(u64, u64)>,
+    /// Index in `inputs` of the next input to give to the game.
+    idx_input: usize,
+    frame: u64,
+    rewind: Rewind,
+}
+
+impl Replay {
+    /// Prepare to replay `recording` on `game`, which should not have been updated yet.
+    pub fn new<RG: Simulator>(recording: Recording, game: &Game<RG>) -> Result<Self, String> {
+        check_recording_header(&recording.header, game)?;
+        Ok(Replay {
+            inputs: try_parse_recording(&recording)?,
+            checksums: recording.checksums,
+            idx_input: 0,
+            frame: 0,
+            rewind: Rewind::default(),
+        })
+    }
+
+    /// The frame that will be played next.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Index of the next input of the recording to be played.
+    pub fn input_index(&self) -> usize {
+        self.idx_input
+    }
+
+    /// Frame of the last input, the recording ends there.
+    pub fn last_frame(&self) -> u64 {
+        // Cannot fail because of the validation in `try_parse_recording`.
+        self.inputs.last().unwrap().0
+    }
+
+    /// Play a single frame. Returns true once the end of the recording is reached, at its
+    /// quit input or after the frame of its last input.
+    pub fn step<RG: Simulator>(&mut self, game: &mut Game<RG>) -> Result<bool, String> {
+        self.rewind.record_frame(self.frame, game);
+        if let Some((frame, input)) = self.inputs.get(self.idx_input).cloned() {
+            if frame == self.frame {
+                self.rewind.record_input(frame, input);
+                if game.input(input) {
+                    return Ok(true);
+                }
+                self.idx_input += 1;
+            }
+        }
+        game.update();
+        check_state_hash(&self.checksums, self.frame, game)?;
+        self.frame += 1;
+        Ok(self.idx_input == self.inputs.len())
+    }
+
+    /// Play frames until the end of the recording, or until the game is over.
+    pub fn run_to_end<RG: Simulator>(&mut self, game: &mut Game<RG>) -> Result<(), String> {
+        while !self.step(game)? && !game.game_over() {}
+        Ok(())
+    }
+
+    /// Jump to the start of frame `target`. Going back is limited by how far the game can
+    /// be rewound, going forward stops at the end of the recording. Returns true if the end
+    /// was reached.
+    pub fn seek<RG: Simulator>(
+        &mut self,
+        game: &mut Game<RG>,
+        target: u64,
+    ) -> Result<bool, String> {
+        if target < self.frame {
+            self.frame = self.rewind.rewind_to(game, self.frame, target)?;
+            self.idx_input = self.inputs.partition_point(|(f, _)| *f < self.frame);
+            return Ok(false);
+        }
+        while self.frame < target {
+            if self.step(game)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::new_game;
+    use crate::sim::RECORDING_VERSION;
+
+    fn recording(inputs: Vec<(u64, char)>) -> Recording {
+        let game = new_game(5);
+        let mut recording = Recording::new(RecordingHeader {
+            version: RECORDING_VERSION,
+            game_version: "test".to_string(),
+            map_file: "map.txt".to_string(),
+            map_hash: game.map().layout_hash(),
+            seed: 5,
+            start_dir: 'l',
+            update_hz: 6,
+            checksum_interval: 0,
+            initial_state: None,
+            campaign: None,
+            trees_hash: None,
+            rules: game.params().rules.clone(),
+        });
+        for input in inputs {
+            recording.push(input);
+        }
+        recording
+    }
+
+    #[test]
+    fn test_seek_matches_playing_straight_through() {
+        let recording = recording(vec![(3, 'u'), (10, 'l'), (40, 'd'), (80, 'q')]);
+        let mut game = new_game(5);
+        let mut hashes = Vec::new();
+        let mut replay = Replay::new(recording.clone(), &game).unwrap();
+        while !replay.step(&mut game).unwrap() {
+            hashes.push(game.state_hash());
+        }
+        assert_eq!(replay.frame(), 80);
+        assert_eq!(replay.input_index(), 3);
+
+        let mut game = new_game(5);
+        let mut replay = Replay::new(recording, &game).unwrap();
+        assert_eq!(replay.seek(&mut game, 50), Ok(false));
+        assert_eq!(game.state_hash(), hashes[49]);
+        assert_eq!(replay.seek(&mut game, 7), Ok(false));
+        assert_eq!((replay.frame(), replay.input_index()), (7, 1));
+        assert_eq!(game.state_hash(), hashes[6]);
+        replay.step(&mut game).unwrap();
+        assert_eq!(game.state_hash(), hashes[7]);
+        assert_eq!(replay.seek(&mut game, 1000), Ok(true));
+        assert_eq!(replay.frame(), replay.last_frame());
+    }
+
+    #[test]
+    fn test_recording_without_quit() {
+        // The game ends before the last input is reached.
+        let mut game = new_game(5);
+        let mut replay = Replay::new(recording(vec![(3, 'u'), (100_000, 'l')]), &game).unwrap();
+        assert_eq!(replay.run_to_end(&mut game), Ok(()));
+        assert!(game.game_over());
+        assert!(replay.frame() < replay.last_frame());
+
+        // Without a game over the recording ends after its last input.
+        let mut game = new_game(5);
+        let mut replay = Replay::new(recording(vec![(3, 'u'), (10, 'l')]), &game).unwrap();
+        assert_eq!(replay.seek(&mut game, 1000), Ok(true));
+        assert_eq!(replay.frame(), 11);
+        assert!(!game.game_over());
+    }
+
+    #[test]
+    fn test_invalid_recordings() {
+        let game = new_game(5);
+        let err = |recording| Replay::new(recording, &game).err().unwrap();
+        assert_eq!(err(recording(vec![])), "Empty recording.");
+        let mut other_map = recording(vec![(3, 'q')]);
+        other_map.header.map_hash += 1;
+        assert!(err(other_map).starts_with("Recording was made on map"));
+        let mut other_trees = recording(vec![(3, 'q')]);
+        other_trees.header.trees_hash = Some(0x123);
+        assert_eq!(
+            err(other_trees),
+            "Recording was made with behaviour trees with hash 0000000000000123, but the game has no behaviour trees."
+        );
+        let mut other_state = recording(vec![(3, 'q')]);
+        other_state.header.initial_state = Some(("save.txt".to_string(), 0xabc));
+        assert_eq!(
+            err(other_state),
+            "Recording was started from save state with hash 0000000000000abc, but the game has no save state."
+        );
+    }
+}
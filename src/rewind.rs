@@ -0,0 +1,168 @@
+//! Stepping backwards in time.
+//!
+//! The game cannot be run in reverse, so instead a save state is stored every few frames
+//! together with all inputs. Going back to a frame loads the closest earlier save state
+//! and replays the inputs from there.
+
+use std::collections::VecDeque;
+
+use crate::game::{Game, Input};
+use crate::sim::Simulator;
+
+/// Frames between the save states kept for rewinding.
+pub const REWIND_INTERVAL: u64 = 30;
+/// Number of save states kept, older ones are dropped.
+pub const REWIND_CAPACITY: usize = 200;
+
+pub struct Rewind {
+    interval: u64,
+    capacity: usize,
+    /// `(frame, save state)` taken at the start of the frame, oldest first.
+    snapshots: VecDeque<(u64, String)>,
+    /// Inputs given at the start of each frame, oldest first.
+    inputs: VecDeque<(u64, Input)>,
+}
+
+impl Rewind {
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        assert!(interval > 0 && capacity > 0);
+        Rewind {
+            interval,
+            capacity,
+            snapshots: VecDeque::new(),
+            inputs: VecDeque::new(),
+        }
+    }
+
+    /// Call at the start of every frame, before the input and update of that frame.
+    pub fn record_frame<RG: Simulator>(&mut self, frame: u64, game: &Game<RG>) {
+        if !frame.is_multiple_of(self.interval) {
+            return;
+        }
+        if self.snapshots.back().is_some_and(|(f, _)| *f >= frame) {
+            return;
+        }
+        self.snapshots.push_back((frame, game.save_state()));
+        if self.snapshots.len() > self.capacity {
+            self.snapshots.pop_front();
+            let oldest = self.oldest_frame().unwrap();
+            while self.inputs.front().is_some_and(|(f, _)| *f < oldest) {
+                self.inputs.pop_front();
+            }
+        }
+    }
+
+    /// Call for every input given to the game in `frame`.
+    pub fn record_input(&mut self, frame: u64, input: Input) {
+        self.inputs.push_back((frame, input));
+    }
+
+    /// Earliest frame that can still be rewound to.
+    pub fn oldest_frame(&self) -> Option<u64> {
+        self.snapshots.front().map(|(f, _)| *f)
+    }
+
+    /// Move `game`, currently at the start of `frame`, back `n` frames. Returns the frame
+    /// the game is at now, which is never before the oldest stored save state.
+    pub fn step_back<RG: Simulator>(
+        &mut self,
+        game: &mut Game<RG>,
+        frame: u64,
+        n: u64,
+    ) -> Result<u64, String> {
+        self.rewind_to(game, frame, frame.saturating_sub(n))
+    }
+
+    /// Move `game`, currently at the start of `frame`, back to the start of `target`.
+    /// Everything recorded after `target` is forgotten, since the game will continue from
+    /// there. Returns the frame the game is at now.
+    pub fn rewind_to<RG: Simulator>(
+        &mut self,
+        game: &mut Game<RG>,
+        frame: u64,
+        target: u64,
+    ) -> Result<u64, String> {
+        let oldest = self
+            .oldest_frame()
+            .ok_or("Nothing recorded to rewind to.")?;
+        let target = target.clamp(oldest, frame);
+
+        while self.snapshots.back().is_some_and(|(f, _)| *f > target) {
+            self.snapshots.pop_back();
+        }
+        let (start, state) = self.snapshots.back().unwrap();
+        game.load_state(state)?;
+
+        let mut inputs = self
+            .inputs
+            .iter()
+            .filter(|(f, _)| *f >= *start && *f < target)
+            .peekable();
+        for f in *start..target {
+            if let Some((_, input)) = inputs.next_if(|(input_frame, _)| *input_frame == f) {
+                game.input(*input);
+            }
+            game.update();
+        }
+
+        while self.inputs.back().is_some_and(|(f, _)| *f >= target) {
+            self.inputs.pop_back();
+        }
+        Ok(target)
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Rewind::new(REWIND_INTERVAL, REWIND_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::new_game;
+
+    #[test]
+    fn test_step_back_restores_earlier_states() {
+        let mut game = new_game(3);
+        let mut rewind = Rewind::new(10, 100);
+        let inputs = [(2, Input::Up), (15, Input::Left), (31, Input::Down)];
+        let mut hashes = Vec::new();
+        for frame in 0..40 {
+            rewind.record_frame(frame, &game);
+            hashes.push(game.state_hash());
+            if let Some((_, input)) = inputs.iter().find(|(f, _)| *f == frame) {
+                rewind.record_input(frame, *input);
+                game.input(*input);
+            }
+            game.update();
+        }
+
+        assert_eq!(rewind.step_back(&mut game, 40, 5), Ok(35));
+        assert_eq!(game.state_hash(), hashes[35]);
+        assert_eq!(rewind.rewind_to(&mut game, 35, 12), Ok(12));
+        assert_eq!(game.state_hash(), hashes[12]);
+
+        // Playing on from the rewound frame and stepping back again works as well.
+        rewind.record_frame(12, &game);
+        game.update();
+        assert_eq!(rewind.step_back(&mut game, 13, 1), Ok(12));
+        assert_eq!(game.state_hash(), hashes[12]);
+    }
+
+    #[test]
+    fn test_step_back_stops_at_oldest_snapshot() {
+        let mut game = new_game(3);
+        let mut rewind = Rewind::new(10, 2);
+        let mut hashes = Vec::new();
+        for frame in 0..35 {
+            rewind.record_frame(frame, &game);
+            hashes.push(game.state_hash());
+            game.update();
+        }
+        assert_eq!(rewind.oldest_frame(), Some(20));
+        assert_eq!(rewind.step_back(&mut game, 35, 100), Ok(20));
+        assert_eq!(game.state_hash(), hashes[20]);
+    }
+}
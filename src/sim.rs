@@ -1,50 +1,516 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
+use crate::game::rules::Rules;
+use crate::game::Input;
+
 pub trait RandGen {
     fn rand(&mut self) -> usize;
+
+    /// Internal state of the generator, so it can be stored in save states.
+    fn state(&self) -> u64;
+
+    fn set_state(&mut self, state: u64);
+}
+
+/// Seed used when none is given on the command line.
+pub const DEFAULT_SEED: u64 = 0;
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+const PCG_INCREMENT: u64 = 1442695040888963407;
+
+/// Small seeded PCG32 (XSH RR) generator.
+///
+/// Implemented here instead of pulling in a crate so the sequence for a given seed
+/// can never change under us, which would break all existing recordings.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg32 { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(PCG_INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+}
+
+impl Default for Pcg32 {
+    fn default() -> Self {
+        Pcg32::new(DEFAULT_SEED)
+    }
 }
 
+impl RandGen for Pcg32 {
+    fn rand(&mut self) -> usize {
+        self.next_u32() as usize
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+}
+
+/// The simulator used by the game: a seeded random generator and a file loader.
+#[derive(Debug, Default)]
+pub struct Sim<L: FileLoader = DiskLoader> {
+    rng: Pcg32,
+    loader: L,
+}
+
+impl<L: FileLoader> Sim<L> {
+    pub fn new(seed: u64, loader: L) -> Self {
+        Sim {
+            rng: Pcg32::new(seed),
+            loader,
+        }
+    }
+}
+
+impl<L: FileLoader> RandGen for Sim<L> {
+    fn rand(&mut self) -> usize {
+        self.rng.rand()
+    }
+
+    fn state(&self) -> u64 {
+        self.rng.state()
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.rng.set_state(state);
+    }
+}
+
+impl<L: FileLoader> FileLoader for Sim<L> {
+    fn load_file(&mut self, filename: &str) -> io::Result<Vec<Vec<char>>> {
+        self.loader.load_file(filename)
+    }
+}
+
+impl<L: FileLoader + Default> Simulator for Sim<L> {}
+
 pub trait FileLoader {
-    fn load_file(&mut self, filename: &str) -> Vec<Vec<char>>;
+    fn load_file(&mut self, filename: &str) -> io::Result<Vec<Vec<char>>>;
 }
 
 pub trait Simulator: RandGen + FileLoader + Default {}
 
-pub type Recording = Vec<(u64, char)>;
+fn split_rows(contents: &str) -> Vec<Vec<char>> {
+    contents
+        .lines()
+        .map(|line| line.chars().collect())
+        .collect()
+}
+
+/// Loads files from disk, relative to the current working directory.
+#[derive(Debug, Default, Clone)]
+pub struct DiskLoader;
+
+impl FileLoader for DiskLoader {
+    fn load_file(&mut self, filename: &str) -> io::Result<Vec<Vec<char>>> {
+        let contents = std::fs::read_to_string(filename).map_err(|e| {
+            io::Error::new(e.kind(), format!("Failed to load '{}': {}", filename, e))
+        })?;
+        Ok(split_rows(&contents))
+    }
+}
+
+/// Serves files from memory, useful for tests that should not touch the disk.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn insert(&mut self, filename: &str, contents: &str) {
+        self.files
+            .insert(filename.to_string(), contents.to_string());
+    }
+}
+
+impl FileLoader for MemoryLoader {
+    fn load_file(&mut self, filename: &str) -> io::Result<Vec<Vec<char>>> {
+        self.files
+            .get(filename)
+            .map(|contents| split_rows(contents))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No file named '{}' in memory.", filename),
+                )
+            })
+    }
+}
+
+/// Version of the recording file format, bump this when the format or the meaning of a
+/// header field changes.
+pub const RECORDING_VERSION: u32 = 3;
+
+/// Everything needed to set up the game exactly like it was when the recording was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub game_version: String,
+    pub map_file: String,
+    pub map_hash: u64,
+    pub seed: u64,
+    pub start_dir: char,
+    pub update_hz: u64,
+    /// Number of frames between stored state checksums, 0 if there are none.
+    pub checksum_interval: u64,
+    /// Save state file the game was started from and the hash of its contents, if it did not
+    /// start from the beginning.
+    pub initial_state: Option<(String, u64)>,
+    /// Campaign file and the hash of its contents, if the game was played as a campaign.
+    pub campaign: Option<(String, u64)>,
+    /// Hash of the behaviour trees of the ghosts, if any ghost has a tree for a brain.
+    pub trees_hash: Option<u64>,
+    /// Rules the game was started with, rules missing from the file have their default.
+    pub rules: Rules,
+}
+
+/// Inputs of a game together with the header needed to reproduce it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub inputs: Vec<(u64, char)>,
+    /// Game state hashes `(frame, hash)`, taken after the update of that frame.
+    pub checksums: Vec<(u64, u64)>,
+}
+
+impl Recording {
+    pub fn new(header: RecordingHeader) -> Self {
+        Recording {
+            header,
+            inputs: Vec::new(),
+            checksums: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, input: (u64, char)) {
+        self.inputs.push(input);
+    }
+
+    pub fn push_checksum(&mut self, frame: u64, hash: u64) {
+        self.checksums.push((frame, hash));
+    }
+
+    /// Forget everything from `frame` on, e.g. after rewinding the game to that frame.
+    pub fn truncate(&mut self, frame: u64) {
+        self.inputs.retain(|(f, _)| *f < frame);
+        self.checksums.retain(|(f, _)| *f < frame);
+    }
+}
+
+/// FNV-1a hasher.
+///
+/// Unlike `DefaultHasher` the output is guaranteed to stay the same across Rust releases
+/// and platforms, so these hashes can be stored in recordings.
+#[derive(Debug, Clone)]
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // The default implementations use native endianness and width.
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Stable hash of any hashable value, see [`Fnv1a`].
+pub fn stable_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = Fnv1a::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
 
 pub fn write_recording_to_file(recording: &Recording, filename: &str) -> io::Result<()> {
     let file = File::create(filename)?;
     let mut writer = BufWriter::new(file);
+    write_file(recording, &mut writer)?;
+    writer.flush()
+}
 
-    for (timestamp, character) in recording {
-        writeln!(writer, "{},{}", timestamp, character)?;
+fn write_file<T: Write>(recording: &Recording, writer: &mut T) -> io::Result<()> {
+    let header = &recording.header;
+    writeln!(writer, "#version={}", header.version)?;
+    writeln!(writer, "#game_version={}", header.game_version)?;
+    writeln!(writer, "#map={}", header.map_file)?;
+    writeln!(writer, "#map_hash={:016x}", header.map_hash)?;
+    writeln!(writer, "#seed={}", header.seed)?;
+    writeln!(writer, "#start_dir={}", header.start_dir)?;
+    writeln!(writer, "#update_hz={}", header.update_hz)?;
+    writeln!(writer, "#checksum_interval={}", header.checksum_interval)?;
+    if let Some((initial_state, hash)) = &header.initial_state {
+        writeln!(writer, "#initial_state={}", initial_state)?;
+        writeln!(writer, "#initial_state_hash={:016x}", hash)?;
+    }
+    if let Some((campaign, hash)) = &header.campaign {
+        writeln!(writer, "#campaign={}", campaign)?;
+        writeln!(writer, "#campaign_hash={:016x}", hash)?;
+    }
+    if let Some(hash) = header.trees_hash {
+        writeln!(writer, "#trees_hash={:016x}", hash)?;
+    }
+    for (key, value) in header.rules.entries().iter() {
+        writeln!(writer, "#rule.{}={}", key, value)?;
     }
 
-    writer.flush()?;
+    // Merge inputs and checksums so the file reads in frame order.
+    let mut checksums = recording.checksums.iter().peekable();
+    for (timestamp, character) in &recording.inputs {
+        while let Some((frame, hash)) = checksums.next_if(|(frame, _)| frame < timestamp) {
+            writeln!(writer, "={},{:016x}", frame, hash)?;
+        }
+        writeln!(writer, "{},{}", timestamp, character)?;
+    }
+    for (frame, hash) in checksums {
+        writeln!(writer, "={},{:016x}", frame, hash)?;
+    }
     Ok(())
 }
 
-pub fn read_recording_from_file(filename: &str) -> io::Result<Recording> {
+pub fn read_recording_from_file(filename: &str, mode: ParseMode) -> io::Result<Recording> {
     let file = File::open(filename)?;
     let mut reader = BufReader::new(file);
-    return parse_file(&mut reader);
+    parse_file(&mut reader, mode)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_header(fields: &HashMap<String, String>) -> io::Result<RecordingHeader> {
+    let field = |key: &str| {
+        fields
+            .get(key)
+            .ok_or_else(|| invalid_data(format!("Recording header is missing '{}'.", key)))
+    };
+    let bad_value = |key: &str, value: &str| {
+        invalid_data(format!(
+            "Invalid value '{}' for '{}' in recording header.",
+            value, key
+        ))
+    };
+    let number = |key: &str| {
+        let value = field(key)?;
+        value.parse::<u64>().map_err(|_| bad_value(key, value))
+    };
+
+    let version =
+        match fields.get("version") {
+            None => return Err(invalid_data(
+                "Recording has no version header, it was made with an older version of the game."
+                    .to_string(),
+            )),
+            Some(value) => value
+                .parse::<u32>()
+                .map_err(|_| bad_value("version", value))?,
+        };
+    if version != RECORDING_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported recording version {}, expected {}.",
+            version, RECORDING_VERSION
+        )));
+    }
+
+    let hash = |key: &str| {
+        let value = field(key)?;
+        u64::from_str_radix(value, 16).map_err(|_| bad_value(key, value))
+    };
+    let map_hash = hash("map_hash")?;
+    let start_dir = {
+        let value = field("start_dir")?;
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return Err(bad_value("start_dir", value)),
+        }
+    };
+
+    let mut rules = Rules::default();
+    for (key, value) in fields {
+        if let Some(rule) = key.strip_prefix("rule.") {
+            rules
+                .set(rule, value)
+                .map_err(|e| invalid_data(format!("Recording header: {}", e)))?;
+        }
+    }
+
+    Ok(RecordingHeader {
+        version,
+        game_version: field("game_version")?.clone(),
+        map_file: field("map")?.clone(),
+        map_hash,
+        seed: number("seed")?,
+        start_dir,
+        update_hz: number("update_hz")?,
+        // Optional, recordings without checksums are still valid.
+        checksum_interval: match fields.get("checksum_interval") {
+            Some(_) => number("checksum_interval")?,
+            None => 0,
+        },
+        initial_state: match fields.get("initial_state") {
+            Some(initial_state) => Some((initial_state.clone(), hash("initial_state_hash")?)),
+            None => None,
+        },
+        campaign: match fields.get("campaign") {
+            Some(campaign) => Some((campaign.clone(), hash("campaign_hash")?)),
+            None => None,
+        },
+        trees_hash: match fields.get("trees_hash") {
+            Some(_) => Some(hash("trees_hash")?),
+            None => None,
+        },
+        rules,
+    })
+}
+
+/// How to deal with lines in a recording that cannot be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first bad line, reporting its line number.
+    Strict,
+    /// Skip bad lines and keep going.
+    Lenient,
+}
+
+enum Line {
+    Header(String, String),
+    Checksum(u64, u64),
+    Input(u64, char),
+}
+
+fn parse_frame(frame_str: &str) -> Result<u64, String> {
+    frame_str
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid frame number '{}'.", frame_str))
+}
+
+fn parse_line(line: &str, mode: ParseMode) -> Result<Line, String> {
+    if let Some(entry) = line.strip_prefix('#') {
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or("Expected a header line '#key=value'.")?;
+        Ok(Line::Header(key.to_string(), value.to_string()))
+    } else if let Some(entry) = line.strip_prefix('=') {
+        let (frame_str, hash_str) = entry
+            .split_once(',')
+            .ok_or("Expected a checksum line '=frame,hash'.")?;
+        let frame = parse_frame(frame_str)?;
+        let hash = u64::from_str_radix(hash_str, 16)
+            .map_err(|_| format!("Invalid checksum '{}'.", hash_str))?;
+        Ok(Line::Checksum(frame, hash))
+    } else {
+        let (frame_str, char_str) = line
+            .split_once(',')
+            .ok_or("Expected an input line 'frame,input'.")?;
+        let frame = parse_frame(frame_str)?;
+        let mut chars = char_str.chars();
+        let character = chars.next().ok_or("Missing input character.")?;
+        if mode == ParseMode::Strict {
+            if chars.next().is_some() {
+                return Err(format!(
+                    "Expected a single input character, got '{}'.",
+                    char_str
+                ));
+            }
+            Input::try_from(character)?;
+        }
+        Ok(Line::Input(frame, character))
+    }
 }
 
-fn parse_file<T: BufRead>(reader: &mut T) -> io::Result<Recording> {
-    let mut recording = Vec::new();
+fn parse_file<T: BufRead>(reader: &mut T, mode: ParseMode) -> io::Result<Recording> {
+    let mut header_fields = HashMap::new();
+    let mut inputs: Vec<(u64, char)> = Vec::new();
+    let mut checksums: Vec<(u64, u64)> = Vec::new();
 
-    for line in reader.lines() {
+    for (i, line) in reader.lines().enumerate() {
         let line = line?;
-        if let Some((timestamp_str, char_str)) = line.split_once(',') {
-            if let (Ok(timestamp), Some(character)) =
-                (timestamp_str.parse::<u64>(), char_str.chars().next())
-            {
-                recording.push((timestamp, character));
+        let line_error = |msg: String| invalid_data(format!("Line {}: {}", i + 1, msg));
+        let parsed = match parse_line(&line, mode) {
+            Ok(parsed) => parsed,
+            Err(e) if mode == ParseMode::Strict => return Err(line_error(e)),
+            Err(_) => continue,
+        };
+        // Replays consume at most one input per frame, so frames must strictly increase.
+        let check_order = |frame: u64, previous: Option<u64>| match previous {
+            Some(previous) if mode == ParseMode::Strict && frame <= previous => Err(line_error(
+                format!("Frame {} does not come after frame {}.", frame, previous),
+            )),
+            _ => Ok(()),
+        };
+        match parsed {
+            // Unknown keys are ignored so newer files stay readable where possible.
+            Line::Header(key, value) => {
+                header_fields.insert(key, value);
+            }
+            Line::Checksum(frame, hash) => {
+                check_order(frame, checksums.last().map(|(f, _)| *f))?;
+                checksums.push((frame, hash));
+            }
+            Line::Input(frame, character) => {
+                check_order(frame, inputs.last().map(|(f, _)| *f))?;
+                inputs.push((frame, character));
             }
         }
     }
-    return Ok(recording);
+    Ok(Recording {
+        header: parse_header(&header_fields)?,
+        inputs,
+        checksums,
+    })
 }
 
 #[cfg(test)]
@@ -52,11 +518,15 @@ mod tests {
     use super::*;
     use std::io::Cursor;
 
+    const HEADER: &str =
+        "#version=3\n#game_version=0.1.0\n#map=map.txt\n#map_hash=00000000000000ff\n\
+                          #seed=42\n#start_dir=l\n#update_hz=6\n";
+
     // Helper function to simplify testing
     fn test_parse_file(name: &str, input: &str, expected: Vec<(u64, char)>) {
-        let mut cursor = Cursor::new(input);
-        let result = parse_file(&mut cursor).unwrap();
-        assert_eq!(result, expected, "Test failed: {}", name);
+        let mut cursor = Cursor::new(format!("{}{}", HEADER, input));
+        let result = parse_file(&mut cursor, ParseMode::Lenient).unwrap();
+        assert_eq!(result.inputs, expected, "Test failed: {}", name);
     }
 
     #[test]
@@ -70,4 +540,162 @@ mod tests {
         test_parse_file("unicode characters",  "100,🎵\n200,ñ\n300,中\n",                     vec![(100, '🎵'), (200, 'ñ'), (300, '中')]);
         test_parse_file("multiple characters", "123,abc\n456,xyz\n",                          vec![(123, 'a'), (456, 'x')]);
     }
+
+    fn test_parse_file_strict(name: &str, input: &str, expected: Result<Vec<(u64, char)>, &str>) {
+        // The header takes up the first 7 lines.
+        let mut cursor = Cursor::new(format!("{}{}", HEADER, input));
+        let result = parse_file(&mut cursor, ParseMode::Strict)
+            .map(|recording| recording.inputs)
+            .map_err(|e| e.to_string());
+        assert_eq!(
+            result,
+            expected.map_err(|e| e.to_string()),
+            "Test failed: {}",
+            name
+        );
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_parse_file_strict_different_inputs() {
+        test_parse_file_strict("valid input",         "1,u\n2,d\n3,q\n",        Ok(vec![(1, 'u'), (2, 'd'), (3, 'q')]));
+        test_parse_file_strict("empty input",         "",                       Ok(vec![]));
+        test_parse_file_strict("malformed line",      "1,u\ninvalid_line\n",    Err("Line 9: Expected an input line 'frame,input'."));
+        test_parse_file_strict("invalid timestamp",   "abc,u\n",                Err("Line 8: Invalid frame number 'abc'."));
+        test_parse_file_strict("empty character",     "1,u\n2,\n",              Err("Line 9: Missing input character."));
+        test_parse_file_strict("unknown character",   "1,u\n2,ñ\n",             Err("Line 9: Invalid input character: 'ñ'"));
+        test_parse_file_strict("multiple characters", "1,ud\n",                 Err("Line 8: Expected a single input character, got 'ud'."));
+        test_parse_file_strict("repeated frame",      "1,u\n1,d\n",             Err("Line 9: Frame 1 does not come after frame 1."));
+        test_parse_file_strict("decreasing frame",    "5,u\n=6,ff\n3,d\n",       Err("Line 10: Frame 3 does not come after frame 5."));
+        test_parse_file_strict("bad checksum",        "=0,xyz\n",               Err("Line 8: Invalid checksum 'xyz'."));
+        test_parse_file_strict("bad header line",     "#oops\n",                Err("Line 8: Expected a header line '#key=value'."));
+    }
+
+    #[test]
+    fn test_parse_file_header() {
+        let mut cursor = Cursor::new(HEADER);
+        let header = parse_file(&mut cursor, ParseMode::Strict).unwrap().header;
+        assert_eq!(
+            header,
+            RecordingHeader {
+                version: RECORDING_VERSION,
+                game_version: "0.1.0".to_string(),
+                map_file: "map.txt".to_string(),
+                map_hash: 0xff,
+                seed: 42,
+                start_dir: 'l',
+                update_hz: 6,
+                checksum_interval: 0,
+                initial_state: None,
+                campaign: None,
+                trees_hash: None,
+                rules: Rules::default(),
+            }
+        );
+
+        let parse_err = |input: &str| {
+            let mut cursor = Cursor::new(input.to_string());
+            parse_file(&mut cursor, ParseMode::Strict)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            parse_err("#seed=42\n1,u\n"),
+            "Recording has no version header, it was made with an older version of the game."
+        );
+        assert_eq!(
+            parse_err(&HEADER.replace("#version=3", "#version=99")),
+            "Unsupported recording version 99, expected 3."
+        );
+        assert_eq!(
+            parse_err(&HEADER.replace("#seed=42\n", "")),
+            "Recording header is missing 'seed'."
+        );
+        assert_eq!(
+            parse_err(&HEADER.replace("#start_dir=l", "#start_dir=left")),
+            "Invalid value 'left' for 'start_dir' in recording header."
+        );
+        assert_eq!(
+            parse_err(&format!("{}#rule.lives=many\n", HEADER)),
+            "Recording header: Invalid value 'many' for 'lives'."
+        );
+    }
+
+    #[test]
+    fn test_parse_file_checksums() {
+        let input = format!(
+            "{}#checksum_interval=10\n=0,00000000000000ff\n5,u\n=10,a\nq,=\n",
+            HEADER
+        );
+        let recording = parse_file(&mut Cursor::new(input), ParseMode::Lenient).unwrap();
+        assert_eq!(recording.header.checksum_interval, 10);
+        assert_eq!(recording.inputs, vec![(5, 'u')]);
+        assert_eq!(recording.checksums, vec![(0, 0xff), (10, 0xa)]);
+    }
+
+    #[test]
+    fn test_write_file_roundtrip() {
+        let mut recording = parse_file(&mut Cursor::new(HEADER), ParseMode::Strict).unwrap();
+        recording.header.checksum_interval = 10;
+        recording.header.initial_state = Some(("save.state.txt".to_string(), 0xdef));
+        recording.header.campaign = Some(("campaign.txt".to_string(), 0xabc));
+        recording.header.trees_hash = Some(0x123);
+        recording.header.rules.score_ghost = 300;
+        recording.push((3, 'u'));
+        recording.push((25, 'q'));
+        recording.push_checksum(0, 1);
+        recording.push_checksum(10, 2);
+        recording.push_checksum(20, 3);
+
+        let mut buffer = Vec::new();
+        write_file(&recording, &mut buffer).unwrap();
+        assert_eq!(
+            parse_file(&mut Cursor::new(buffer), ParseMode::Strict).unwrap(),
+            recording
+        );
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        // Fixed values, these must never change or all recordings become invalid.
+        assert_eq!(stable_hash(&[] as &[u8]), 0xa8c7f832281a39c5);
+        assert_eq!(
+            stable_hash(&vec![vec!['#', '.']]),
+            stable_hash(&vec![vec!['#', '.']])
+        );
+        assert_ne!(
+            stable_hash(&vec![vec!['#', '.']]),
+            stable_hash(&vec![vec!['.', '#']])
+        );
+    }
+
+    #[test]
+    fn test_file_loaders() {
+        let mut loader = MemoryLoader::default();
+        loader.insert("map.txt", "###\n#.#\n");
+        assert_eq!(
+            loader.load_file("map.txt").unwrap(),
+            vec![vec!['#', '#', '#'], vec!['#', '.', '#']]
+        );
+        assert!(loader.load_file("other.txt").is_err());
+
+        assert!(DiskLoader.load_file("does_not_exist.txt").is_err());
+        assert!(!DiskLoader.load_file("map.txt").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pcg32_is_deterministic() {
+        let sequence = |seed| {
+            let mut rng = Pcg32::new(seed);
+            (0..16).map(|_| rng.rand()).collect::<Vec<_>>()
+        };
+        assert_eq!(sequence(7), sequence(7));
+        assert_ne!(sequence(7), sequence(8));
+
+        let mut rng = Pcg32::new(7);
+        rng.rand();
+        let mut restored = Pcg32::default();
+        restored.set_state(rng.state());
+        assert_eq!(rng.rand(), restored.rand());
+    }
 }
@@ -1,24 +1,42 @@
-use crate::game::map::Tile;
-use crate::game::Direction;
-use crate::game::Game;
-use crate::sim::Simulator;
 use graphics::{circle_arc::CircleArc, image::Image, rectangle::Rectangle, types::Color, Context};
 use opengl_graphics::GlGraphics;
 use opengl_graphics::Texture;
+use rust_gaem::game::ghost::Ghost;
+use rust_gaem::game::map::{Map, Tile};
+use rust_gaem::game::speed::TILE;
+use rust_gaem::game::Direction;
+use rust_gaem::game::{Event, Game};
+use rust_gaem::sim::Simulator;
 use std::path::Path;
 use texture::TextureSettings;
 
+/// Game ticks a score popup stays on screen.
+const POPUP_TICKS: u32 = 6;
+
+/// Points shown where they were scored, `tick` is when that happened.
+struct Popup {
+    points: u32,
+    x: i32,
+    y: i32,
+    tick: u32,
+}
+
 pub struct View {
     wall_color: Color,
     ghost_textures: [Texture; 4],
     frightened: Texture,
+    eyes: Texture,
     numbers: Vec<Texture>,
     pacmans: [Texture; 4],
-    fruits: [Texture; 20],
+    fruits: [Texture; 8],
     dot_color: Color,
     tile_size: f64,
     x_offset: f64,
     y_offset: f64,
+    popups: Vec<Popup>,
+    last_tick: u32,
+    /// Show how close the ghosts in the house are to leaving.
+    debug: bool,
 }
 
 fn load_image(name: &str) -> Texture {
@@ -26,7 +44,7 @@ fn load_image(name: &str) -> Texture {
         Path::new(&format!("images/{}.png", name)),
         &TextureSettings::new(),
     )
-    .expect(&format!("Failed to load: {}", name))
+    .unwrap_or_else(|_| panic!("Failed to load: {}", name))
 }
 
 impl View {
@@ -44,6 +62,7 @@ impl View {
             ]
         };
         let frightened = load_image("frightened");
+        let eyes = load_image("eyes");
         let numbers = (0..10)
             .map(|i| load_image(&i.to_string()))
             .collect::<Vec<_>>();
@@ -53,32 +72,22 @@ impl View {
             load_image("pacman_down"),
             load_image("pacman_left"),
         ];
+        // In the order of `Fruit::ALL`.
         let fruits = [
             load_image("cherry"),
             load_image("strawberry"),
             load_image("orange"),
-            load_image("orange"),
-            load_image("apple"),
             load_image("apple"),
             load_image("mellon"),
-            load_image("mellon"),
-            load_image("flower"),
             load_image("flower"),
             load_image("bell"),
-            load_image("bell"),
-            load_image("key"),
-            load_image("key"),
-            load_image("key"),
-            load_image("key"),
-            load_image("key"),
-            load_image("key"),
-            load_image("key"),
             load_image("key"),
         ];
         View {
             wall_color: [0.1294, 0.1294, 0.8706, 1.0],
             ghost_textures,
             frightened,
+            eyes,
             numbers,
             pacmans,
             fruits,
@@ -86,9 +95,36 @@ impl View {
             tile_size: 20.0,
             x_offset: 0.0,
             y_offset: 0.0,
+            popups: Vec::new(),
+            last_tick: 0,
+            debug: false,
         }
     }
 
+    /// Pick up the events of the game since the last call, and forget popups that are
+    /// too old.
+    pub fn update<RG: Simulator>(&mut self, game: &Game<RG>) {
+        let tick = game.ticks();
+        if tick < self.last_tick {
+            // Rewound or jumped back, the popups are from a future that did not happen.
+            self.popups.clear();
+        } else if tick > self.last_tick {
+            self.popups.retain(|p| tick - p.tick < POPUP_TICKS);
+            for event in game.events() {
+                match *event {
+                    Event::GhostEaten { points, x, y } | Event::FruitEaten { points, x, y } => {
+                        self.popups.push(Popup { points, x, y, tick })
+                    }
+                }
+            }
+        }
+        self.last_tick = tick;
+    }
+
+    pub fn toggle_debug(&mut self) {
+        self.debug = !self.debug;
+    }
+
     pub fn resize(&mut self, x: f64, y: f64, map_width: usize, map_height: usize) {
         self.tile_size = y / (map_height + 6) as f64;
         let blankspace = x - (map_width as f64 * self.tile_size);
@@ -132,12 +168,13 @@ impl View {
                             self.tile_size / 4.0,
                             self.tile_size / 4.0,
                         ]);
-                        CircleArc::new(self.dot_color, self.tile_size / 4.0, 0.0, 2.0 * 3.14).draw(
-                            sqr,
-                            &c.draw_state,
-                            c.transform,
-                            g,
-                        );
+                        CircleArc::new(
+                            self.dot_color,
+                            self.tile_size / 4.0,
+                            0.0,
+                            2.0 * std::f64::consts::PI,
+                        )
+                        .draw(sqr, &c.draw_state, c.transform, g);
                     }
                     _ => (),
                 }
@@ -150,30 +187,18 @@ impl View {
         let stats = game.get_stats();
         {
             // Stats
-            let mut sc = stats.score;
-            let mut i = -1;
-            while sc > 0 {
-                sc /= 10;
-                i += 1;
-            }
-            sc = stats.score;
-            while sc > 0 {
-                let d = sc % 10;
-                let sq = offset([
-                    (game.map().width / 2) as f64 * self.tile_size
-                        + i as f64 * self.tile_size * 1.702,
-                    self.tile_size * -2.0,
-                    self.tile_size,
-                    self.tile_size * 1.702,
-                ]);
-                Image::new().rect(sq).draw(
-                    &self.numbers[d as usize],
-                    &c.draw_state,
-                    c.transform,
+            if stats.score > 0 {
+                self.draw_number(
+                    stats.score as u64,
+                    offset([
+                        (game.map().width / 2) as f64 * self.tile_size,
+                        self.tile_size * -2.0,
+                        self.tile_size,
+                        self.tile_size * 1.702,
+                    ]),
+                    c,
                     g,
                 );
-                sc = sc / 10;
-                i -= 1;
             }
 
             for i in 0..stats.lives {
@@ -184,40 +209,52 @@ impl View {
                     .draw(&self.pacmans[3], &c.draw_state, c.transform, g);
             }
 
-            let cap_at_20 = |i| if i > 20 { 20 } else { i };
-            let cap_at_13 = |i| if i > 13 { 13 } else { i };
-            self.fruits[cap_at_13(stats.level.saturating_sub(7))..cap_at_20(stats.level)]
-                .iter()
-                .enumerate()
-                .for_each(|(i, t)| {
-                    Image::new()
-                        .rect(offset(self.entity_sq(
-                            (game.map().width - i - 1) as i32,
-                            (game.map().height + 1) as i32,
-                        )))
-                        .draw(t, &c.draw_state, c.transform, g)
-                });
+            // The fruits of the last 7 levels, oldest on the right.
+            let levels = stats.level.saturating_sub(6).max(1)..=stats.level;
+            for (i, level) in levels.enumerate() {
+                Image::new()
+                    .rect(offset(self.entity_sq(
+                        (game.map().width - i - 1) as i32,
+                        (game.map().height + 1) as i32,
+                    )))
+                    .draw(
+                        &self.fruits[game.fruit(level) as usize],
+                        &c.draw_state,
+                        c.transform,
+                        g,
+                    );
+            }
+        }
+
+        if let Some((fruit, (x, y))) = game.bonus_fruit() {
+            Image::new().rect(offset(self.entity_sq(x, y))).draw(
+                &self.fruits[fruit as usize],
+                &c.draw_state,
+                c.transform,
+                g,
+            );
         }
 
         if stats.lives > 0 {
             // pacman
             let (x, y, d) = game.get_player();
+            let (moving, progress) = game.player_progress();
             let pac_texture = match d {
                 Direction::Up => &self.pacmans[0],
                 Direction::Right => &self.pacmans[1],
                 Direction::Down => &self.pacmans[2],
                 Direction::Left => &self.pacmans[3],
             };
-            Image::new().rect(offset(self.entity_sq(x, y))).draw(
-                pac_texture,
-                &c.draw_state,
-                c.transform,
-                g,
-            );
+            let sq = self.moving_sq(game.map(), (x, y), moving.to_vector(), progress);
+            Image::new()
+                .rect(offset(sq))
+                .draw(pac_texture, &c.draw_state, c.transform, g);
         }
 
-        let pick_color = |c| {
-            if game.frightened() {
+        let pick_color = |ghost: &Ghost, c| {
+            if ghost.eaten() {
+                &self.eyes
+            } else if ghost.frightened() {
                 &self.frightened
             } else {
                 c
@@ -225,14 +262,32 @@ impl View {
         };
 
         for (i, ghost) in game.get_ghosts().iter().enumerate() {
-            Image::new()
-                .rect(offset(self.entity_sq(ghost.x(), ghost.y())))
-                .draw(
-                    pick_color(&self.ghost_textures[i]),
-                    &c.draw_state,
-                    c.transform,
-                    g,
-                );
+            let sq = self.moving_sq(
+                game.map(),
+                (ghost.x(), ghost.y()),
+                ghost.heading(),
+                ghost.progress(),
+            );
+            Image::new().rect(offset(sq)).draw(
+                pick_color(ghost, &self.ghost_textures[i]),
+                &c.draw_state,
+                c.transform,
+                g,
+            );
+        }
+
+        for popup in &self.popups {
+            let size = self.tile_size / 2.0;
+            let width = popup.points.to_string().len() as f64 * size * 1.702;
+            let mut sq = offset(self.entity_sq(popup.x, popup.y));
+            sq[0] += (self.tile_size - width) / 2.0;
+            sq[2] = size;
+            sq[3] = size * 1.702;
+            self.draw_number(popup.points as u64, sq, c, g);
+        }
+
+        if self.debug {
+            self.draw_house_counters(game, c, g);
         }
 
         // DEBUG
@@ -252,6 +307,77 @@ impl View {
         // }
     }
 
+    /// Dots every ghost in the house still needs before it leaves, above the ghost, and
+    /// the ticks left before one leaves because Pac-Man is not eating, below the map.
+    fn draw_house_counters<RG: Simulator>(&self, game: &Game<RG>, c: &Context, g: &mut GlGraphics) {
+        let size = self.tile_size / 2.0;
+        let number_sq = |x: i32, y: i32| {
+            let mut sq = self.entity_sq(x, y);
+            sq[0] += self.x_offset;
+            sq[1] += self.y_offset - size * 1.702;
+            sq[2] = size;
+            sq[3] = size * 1.702;
+            sq
+        };
+        let ghosts = game.get_ghosts();
+        for (ghost, counter) in ghosts.iter().zip(game.house_counters().iter()) {
+            if let Some((dots, limit)) = counter {
+                let left = limit.saturating_sub(*dots);
+                self.draw_number(left as u64, number_sq(ghost.x(), ghost.y()), c, g);
+            }
+        }
+        let (idle, limit) = game.idle_ticks();
+        if limit > 0 {
+            let map = game.map();
+            let sq = number_sq((map.width / 2) as i32, (map.height + 2) as i32);
+            self.draw_number(limit.saturating_sub(idle) as u64, sq, c, g);
+        }
+    }
+
+    /// Frame counter and index of the next recorded input, shown while replaying.
+    pub fn draw_replay_info(
+        &self,
+        frame: u64,
+        input_index: usize,
+        map_width: usize,
+        c: &Context,
+        g: &mut GlGraphics,
+    ) {
+        let size = self.tile_size / 2.0;
+        let y = self.y_offset - self.tile_size * 2.0;
+        self.draw_number(frame, [self.x_offset, y, size, size * 1.702], c, g);
+        let index_x = self.x_offset + map_width as f64 * self.tile_size
+            - input_index.to_string().len() as f64 * size * 1.702;
+        self.draw_number(input_index as u64, [index_x, y, size, size * 1.702], c, g);
+    }
+
+    /// Draw `n` with its first digit in `first_digit`, the next digits follow to the right.
+    fn draw_number(&self, n: u64, first_digit: [f64; 4], c: &Context, g: &mut GlGraphics) {
+        let digits = n.to_string();
+        for (i, d) in digits.bytes().enumerate() {
+            let mut sq = first_digit;
+            sq[0] += i as f64 * first_digit[3];
+            Image::new().rect(sq).draw(
+                &self.numbers[(d - b'0') as usize],
+                &c.draw_state,
+                c.transform,
+                g,
+            );
+        }
+    }
+
+    /// Square of an entity on `pos` that is `progress` on its way to the next tile in
+    /// direction `dir`, unless a wall is in the way.
+    fn moving_sq(&self, map: &Map, pos: (i32, i32), dir: (i32, i32), progress: u32) -> [f64; 4] {
+        let mut sq = self.entity_sq(pos.0, pos.1);
+        if !map.is_wall(pos.0 + dir.0, pos.1 + dir.1) {
+            let part = progress.min(TILE) as f64 / TILE as f64 * self.tile_size;
+            sq[0] += dir.0 as f64 * part;
+            sq[1] += dir.1 as f64 * part;
+        }
+        sq
+    }
+
     fn entity_sq(&self, x: i32, y: i32) -> [f64; 4] {
         [
             x as f64 * self.tile_size,